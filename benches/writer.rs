@@ -0,0 +1,143 @@
+//! Measures writer-thread throughput per flush policy and under contention.
+//!
+//! `baseline` is a copy of the writer before flush policies were added: a
+//! mutex-guarded sender and a thread that writes and flushes every event on
+//! its own into the unbuffered file. The policies coalesce queued events into
+//! larger writes through a buffered file.
+
+use alumy::log::{FlushPolicy, NonBlockingWriter};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use std::fs::File;
+use std::io::{BufWriter, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tracing_subscriber::fmt::MakeWriter;
+
+const EVENTS: u64 = 10_000;
+const LINE: &[u8] = b"2026-10-17 13:00:00.000  INFO bench: a typical log line with some payload\n";
+
+fn log_events(policy: FlushPolicy) {
+    let path = std::env::temp_dir().join(format!("alumy-bench-{}.log", std::process::id()));
+    let file = BufWriter::new(File::create(&path).unwrap());
+    let (writer, guard) = NonBlockingWriter::with_flush_policy(file, policy);
+
+    for _ in 0..EVENTS {
+        let mut handle = writer.make_writer();
+        handle.write_all(LINE).unwrap();
+    }
+
+    // Dropping the guard waits until the writer thread has drained the queue.
+    drop(guard);
+    let _ = std::fs::remove_file(path);
+}
+
+type SharedSender = Arc<Mutex<Option<crossbeam::channel::Sender<Vec<u8>>>>>;
+
+/// The event handle of the baseline writer, sending its buffer when dropped.
+struct BaselineHandle {
+    sender: SharedSender,
+    buffer: Vec<u8>,
+}
+
+impl Write for BaselineHandle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if !self.buffer.is_empty() {
+            if let Ok(guard) = self.sender.lock() {
+                if let Some(ref sender) = *guard {
+                    let _ = sender.send(std::mem::take(&mut self.buffer));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BaselineHandle {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+fn log_events_baseline() {
+    let path = std::env::temp_dir().join(format!("alumy-bench-baseline-{}.log", std::process::id()));
+    let mut file = File::create(&path).unwrap();
+    let (sender, receiver) = crossbeam::channel::unbounded::<Vec<u8>>();
+    let sender: SharedSender = Arc::new(Mutex::new(Some(sender)));
+
+    let thread = thread::Builder::new()
+        .name("tracing-writer".to_string())
+        .stack_size(2 * 1024 * 1024)
+        .spawn(move || {
+            for msg in receiver {
+                let _ = file.write_all(&msg);
+                let _ = file.flush();
+            }
+        })
+        .unwrap();
+
+    for _ in 0..EVENTS {
+        let mut handle = BaselineHandle {
+            sender: sender.clone(),
+            buffer: Vec::with_capacity(256),
+        };
+        handle.write_all(LINE).unwrap();
+    }
+
+    *sender.lock().unwrap() = None;
+    thread.join().unwrap();
+    let _ = std::fs::remove_file(path);
+}
+
+fn bench_flush_policies(c: &mut Criterion) {
+    let mut group = c.benchmark_group("flush_policy");
+    group.throughput(Throughput::Elements(EVENTS));
+    group.bench_function("baseline", |b| b.iter(log_events_baseline));
+
+    let policies = [
+        ("every_event", FlushPolicy::EveryEvent),
+        ("drained", FlushPolicy::Drained),
+        ("bytes_64k", FlushPolicy::Bytes(64 * 1024)),
+        ("interval_100ms", FlushPolicy::Interval(Duration::from_millis(100))),
+    ];
+
+    for (name, policy) in policies {
+        group.bench_with_input(BenchmarkId::from_parameter(name), &policy, |b, &policy| {
+            b.iter(|| log_events(policy));
+        });
+    }
+
+    group.finish();
+}
+
+fn bench_concurrent_writers(c: &mut Criterion) {
+    const THREADS: u64 = 8;
+
+    let mut group = c.benchmark_group("concurrent_writers");
+    group.throughput(Throughput::Elements(EVENTS * THREADS));
+    group.bench_function("8_threads", |b| {
+        b.iter(|| {
+            let (writer, guard) = NonBlockingWriter::new(std::io::sink());
+            std::thread::scope(|s| {
+                for _ in 0..THREADS {
+                    s.spawn(|| {
+                        for _ in 0..EVENTS {
+                            let mut handle = writer.make_writer();
+                            handle.write_all(LINE).unwrap();
+                        }
+                    });
+                }
+            });
+            drop(guard);
+        });
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_flush_policies, bench_concurrent_writers);
+criterion_main!(benches);
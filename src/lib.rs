@@ -5,21 +5,74 @@
 //! ## Modules
 //!
 //! - [`log`]: High-performance, non-blocking logging utilities with fluent configuration.
-//! - [`sys`]: System-level utilities like uptime.
+//! - [`sys`]: System-level utilities like uptime, host identity and clock jump detection.
 //! - [`fs`]: Filesystem utilities including size parsing and path building.
 //! - [`version`]: Crate metadata and version information.
+//! - `testing`: Log capture and fixtures for tests (requires the `testing` feature).
 //!
 //! ## Re-exports
 //!
 //! This crate re-exports logging macros from `tracing` for convenience:
-//! `trace!`, `debug!`, `info!`, `warn!`, `error!`, and the `Level` enum.
+//! `trace!`, `debug!`, `info!`, `warn!`, `error!`, and the `Level` enum, next to
+//! [`hexdump!`] for byte buffers.
 
 pub mod version;
 pub mod fs;
 pub mod sys;
 pub mod log;
+#[cfg(any(test, feature = "testing"))]
+pub mod testing;
 
 #[doc(inline)]
 pub use log::LogConfig;
 
 pub use tracing::{trace, debug, info, warn, error, Level};
+
+#[doc(hidden)]
+pub use tracing::{enabled as __enabled, event as __event};
+
+/// Logs a byte buffer as a hex dump, e.g. `hexdump!(Level::DEBUG, frame, "rx frame")`.
+///
+/// The event has `context` as its message and the fields `len` and `hex`, the
+/// latter formatted by [`HexDump`](crate::log::HexDump). Its options can follow
+/// as `key = value`: `compact = true` for a single line, `max_len = 64` and
+/// `marker = "[cut]"` for truncation. An optional `target:` comes first, as in
+/// the other macros. When the level is disabled the buffer is not evaluated.
+///
+/// ```
+/// use alumy::{hexdump, Level};
+///
+/// let frame = [0x02, 0x31, 0x03];
+/// hexdump!(Level::DEBUG, frame, "rx frame");
+/// hexdump!(target: "can", Level::TRACE, &frame[..2], "tx frame", compact = true, max_len = 8);
+/// ```
+#[macro_export]
+macro_rules! hexdump {
+    (target: $target:expr, $level:expr, $data:expr, $context:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::__enabled!(target: $target, $level) {
+            match ::core::convert::AsRef::<[u8]>::as_ref(&$data) {
+                data => $crate::__event!(
+                    target: $target,
+                    $level,
+                    len = data.len(),
+                    hex = %$crate::log::HexDump::new(data)$(.$key($value))*,
+                    "{}",
+                    $context
+                ),
+            }
+        }
+    };
+    ($level:expr, $data:expr, $context:expr $(, $key:ident = $value:expr)* $(,)?) => {
+        if $crate::__enabled!($level) {
+            match ::core::convert::AsRef::<[u8]>::as_ref(&$data) {
+                data => $crate::__event!(
+                    $level,
+                    len = data.len(),
+                    hex = %$crate::log::HexDump::new(data)$(.$key($value))*,
+                    "{}",
+                    $context
+                ),
+            }
+        }
+    };
+}
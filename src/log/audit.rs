@@ -0,0 +1,554 @@
+//! Tamper-evident audit trail.
+//!
+//! Events whose target matches the one given to
+//! [`LogConfig::with_audit`](super::LogConfig::with_audit) are written to a
+//! separate rolling file. Every line starts with an HMAC-SHA256, keyed with the
+//! [`AuditKey`], over the previous line's hash and its own text:
+//!
+//! ```text
+//! #prev 9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08
+//! 5e884898da28047151d0e56f8dc6292773603d0d6aabbdd62a11ef721d1542d8 2026-10-17 13:00:00.123  INFO audit: user login user="alice"
+//! ```
+//!
+//! Each file, including a file reopened after a restart, starts with a `#prev`
+//! line carrying the last hash written before it, so the chain continues across
+//! rotations. [`verify`] recomputes the chain with the same key and reports the
+//! first broken link; without the key, an edited line cannot be given a valid
+//! hash. Removing lines from the end of the newest file cannot be detected from
+//! the files alone: store [`AuditReport::last_hash`] elsewhere and compare it
+//! on the next check.
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use tracing::span::{Attributes, Id, Record};
+use tracing::subscriber::Interest;
+use tracing::level_filters::LevelFilter;
+use tracing::{Dispatch, Event, Metadata, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+use super::rolling::{rotated_files, HeaderFn};
+use super::writer::Sink;
+
+type Hash = [u8; 32];
+
+const HEADER_PREFIX: &str = "#prev ";
+
+/// The secret keying the audit chain (see [`LogConfig::with_audit`](super::LogConfig::with_audit)).
+///
+/// Keep it out of reach of whoever can write the audit files, e.g. in a
+/// secrets store; anyone holding it can forge a valid chain.
+#[derive(Clone, PartialEq, Eq)]
+pub struct AuditKey(Vec<u8>);
+
+impl AuditKey {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+}
+
+impl fmt::Debug for AuditKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AuditKey(..)")
+    }
+}
+
+/// Returns `true` if `target` is the audit target `audit` or a module below it.
+pub(crate) fn is_audit_target(audit: &str, target: &str) -> bool {
+    target.strip_prefix(audit).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Passes to `inner` either only the audit events, or only the others.
+///
+/// Unlike a per-layer filter, this can be swapped out when the logger is reloaded.
+pub(crate) struct AuditRoute<L> {
+    inner: L,
+    target: Option<String>,
+    audit: bool,
+}
+
+impl<L> AuditRoute<L> {
+    /// Routes the events of the audit target `target` to `inner`.
+    pub(crate) fn audit(inner: L, target: &str) -> Self {
+        Self { inner, target: Some(target.to_string()), audit: true }
+    }
+
+    /// Routes every event except those of the audit target `target`, if any, to `inner`.
+    pub(crate) fn regular(inner: L, target: Option<&str>) -> Self {
+        Self { inner, target: target.map(str::to_string), audit: false }
+    }
+
+    fn routes(&self, meta: &Metadata<'_>) -> bool {
+        self.target.as_deref().is_some_and(|t| is_audit_target(t, meta.target())) == self.audit
+    }
+}
+
+impl<S: Subscriber, L: Layer<S>> Layer<S> for AuditRoute<L> {
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, meta: &'static Metadata<'static>) -> Interest {
+        self.inner.register_callsite(meta)
+    }
+
+    fn enabled(&self, meta: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.enabled(meta, ctx)
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(span, values, ctx);
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        if self.routes(event.metadata()) {
+            self.inner.on_event(event, ctx);
+        }
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+}
+
+/// Lets the events and spans of the audit target through the filter `inner`.
+///
+/// An `EnvFilter` directive would also enable targets that merely start with
+/// the audit target, such as `auditor`. The global max level is left to
+/// `inner`, so audit events more verbose than every enabled level are still
+/// skipped by the `tracing` macros.
+pub(crate) struct AuditBypass<L> {
+    inner: L,
+    target: Option<String>,
+}
+
+impl<L> AuditBypass<L> {
+    pub(crate) fn new(inner: L, target: Option<&str>) -> Self {
+        Self { inner, target: target.map(str::to_string) }
+    }
+
+    fn bypasses(&self, meta: &Metadata<'_>) -> bool {
+        self.target.as_deref().is_some_and(|t| is_audit_target(t, meta.target()))
+    }
+}
+
+impl<S: Subscriber, L: Layer<S>> Layer<S> for AuditBypass<L> {
+    fn on_register_dispatch(&self, subscriber: &Dispatch) {
+        self.inner.on_register_dispatch(subscriber);
+    }
+
+    fn on_layer(&mut self, subscriber: &mut S) {
+        self.inner.on_layer(subscriber);
+    }
+
+    fn register_callsite(&self, meta: &'static Metadata<'static>) -> Interest {
+        if self.bypasses(meta) {
+            return Interest::always();
+        }
+        self.inner.register_callsite(meta)
+    }
+
+    fn enabled(&self, meta: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+        self.bypasses(meta) || self.inner.enabled(meta, ctx)
+    }
+
+    fn max_level_hint(&self) -> Option<LevelFilter> {
+        self.inner.max_level_hint()
+    }
+
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_new_span(attrs, id, ctx);
+    }
+
+    fn on_record(&self, span: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        self.inner.on_record(span, values, ctx);
+    }
+
+    fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+        self.inner.on_follows_from(span, follows, ctx);
+    }
+
+    fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+        self.bypasses(event.metadata()) || self.inner.event_enabled(event, ctx)
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        self.inner.on_event(event, ctx);
+    }
+
+    fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_enter(id, ctx);
+    }
+
+    fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+        self.inner.on_exit(id, ctx);
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        self.inner.on_close(id, ctx);
+    }
+
+    fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+        self.inner.on_id_change(old, new, ctx);
+    }
+}
+
+/// Chain position shared between an [`AuditSink`] and the header of its files.
+pub(crate) struct Chain {
+    key: AuditKey,
+    /// Hash of the last record handed to the file.
+    last: Hash,
+    /// `last` before the records being written, where a new file starts.
+    base: Hash,
+}
+
+impl Chain {
+    /// Continues the chain of an existing audit trail at `path`, if any.
+    pub(crate) fn resume(path: &Path, key: AuditKey) -> io::Result<Self> {
+        let mut candidates = vec![path.to_path_buf()];
+        candidates.extend(rotated_files(path)?.into_iter().rev());
+
+        for file in candidates {
+            let content = match fs::read_to_string(&file) {
+                Ok(content) => content,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+            if let Some(last) = content.lines().rev().find_map(|line| parse_line(line).map(|(hash, _)| hash)) {
+                return Ok(Self { key, last, base: last });
+            }
+        }
+        Ok(Self { key, last: Hash::default(), base: Hash::default() })
+    }
+}
+
+/// Returns the header that starts every audit file, linking it to the previous one.
+pub(crate) fn audit_header(chain: Arc<Mutex<Chain>>) -> HeaderFn {
+    Arc::new(move || {
+        let chain = chain.lock().unwrap_or_else(|e| e.into_inner());
+        format!("{HEADER_PREFIX}{}\n", hex(&chain.base)).into_bytes()
+    })
+}
+
+/// Prefixes every line with its chained hash.
+pub(crate) struct AuditSink<S> {
+    inner: S,
+    chain: Arc<Mutex<Chain>>,
+    partial: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl<S: Sink> AuditSink<S> {
+    pub(crate) fn new(inner: S, chain: Arc<Mutex<Chain>>) -> Self {
+        Self {
+            inner,
+            chain,
+            partial: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl<S: Sink> Write for AuditSink<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.partial.extend_from_slice(buf);
+        let Some(end) = self.partial.iter().rposition(|&b| b == b'\n') else {
+            return Ok(buf.len());
+        };
+        let lines: Vec<u8> = self.partial.drain(..=end).collect();
+
+        let mut chain = self.chain.lock().unwrap_or_else(|e| e.into_inner());
+        let mut last = chain.last;
+        for line in lines.split(|&b| b == b'\n').filter(|l| !l.is_empty()) {
+            // Hashes are computed over the text as it ends up in the file.
+            let text = String::from_utf8_lossy(line);
+            last = link(&chain.key, &last, &text);
+            let _ = writeln!(self.output, "{} {text}", hex(&last));
+        }
+        drop(chain);
+
+        let result = self.inner.write_all(&self.output);
+        self.output.clear();
+
+        chain = self.chain.lock().unwrap_or_else(|e| e.into_inner());
+        if result.is_ok() {
+            chain.last = last;
+        }
+        chain.base = chain.last;
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Sink> Sink for AuditSink<S> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.inner.sync_data()
+    }
+}
+
+/// Where [`verify`] found the chain broken.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub file: PathBuf,
+    /// 1-based line number within `file`.
+    pub line: usize,
+    pub reason: String,
+}
+
+impl fmt::Display for BrokenLink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}: {}", self.file.display(), self.line, self.reason)
+    }
+}
+
+/// Result of [`verify`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// Files checked, oldest first.
+    pub files: Vec<PathBuf>,
+    /// Records whose hash was verified before the first broken link.
+    pub records: usize,
+    /// The first broken link, if any.
+    pub broken: Option<BrokenLink>,
+    /// Hex hash of the last verified record or header.
+    ///
+    /// Keep it outside the audit files: if a later check ends before reaching
+    /// it, records were cut off the end.
+    pub last_hash: Option<String>,
+}
+
+impl AuditReport {
+    pub fn is_intact(&self) -> bool {
+        self.broken.is_none()
+    }
+}
+
+/// Verifies the hash chain of the audit trail whose active file is `path`,
+/// together with its rotated files, with the `key` it was written with.
+///
+/// The oldest file anchors the chain; an edited, inserted or removed line, or
+/// a missing rotated file, is reported as the first broken link. Records cut
+/// off the end show only as a [`last_hash`](AuditReport::last_hash) other than
+/// the one kept from an earlier check.
+///
+/// # Examples
+///
+/// ```no_run
+/// use alumy::log::audit::AuditKey;
+///
+/// let report = alumy::log::audit::verify("logs/audit.log", &AuditKey::new(*b"secret from the vault"))?;
+/// if let Some(broken) = &report.broken {
+///     eprintln!("audit trail tampered with: {broken}");
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn verify(path: impl AsRef<Path>, key: &AuditKey) -> io::Result<AuditReport> {
+    let path = path.as_ref();
+    let mut files = rotated_files(path)?;
+    if path.exists() {
+        files.push(path.to_path_buf());
+    }
+
+    let mut report = AuditReport {
+        files: files.clone(),
+        records: 0,
+        broken: None,
+        last_hash: None,
+    };
+    let mut last: Option<Hash> = None;
+
+    for file in files {
+        let reader = BufReader::new(fs::File::open(&file)?);
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            let broken = |reason: &str| BrokenLink {
+                file: file.clone(),
+                line: i + 1,
+                reason: reason.to_string(),
+            };
+
+            if let Some(prev) = line.strip_prefix(HEADER_PREFIX) {
+                let Some(prev) = unhex(prev) else {
+                    report.broken = Some(broken("malformed chain header"));
+                    return Ok(report);
+                };
+                match last {
+                    Some(last) if last != prev => {
+                        report.broken = Some(broken("chain header does not match the previous record"));
+                        return Ok(report);
+                    }
+                    _ => last = Some(prev),
+                }
+                report.last_hash = Some(hex(&prev));
+                continue;
+            }
+
+            let Some((hash, text)) = parse_line(&line) else {
+                report.broken = Some(broken("malformed record"));
+                return Ok(report);
+            };
+            let Some(prev) = last else {
+                report.broken = Some(broken("record before the chain header"));
+                return Ok(report);
+            };
+            if link(key, &prev, text) != hash {
+                report.broken = Some(broken("hash mismatch: record edited, inserted or removed"));
+                return Ok(report);
+            }
+            last = Some(hash);
+            report.last_hash = Some(hex(&hash));
+            report.records += 1;
+        }
+    }
+    Ok(report)
+}
+
+fn link(key: &AuditKey, prev: &Hash, text: &str) -> Hash {
+    let mut mac = Hmac::<Sha256>::new_from_slice(&key.0).expect("HMAC accepts keys of any length");
+    mac.update(prev);
+    mac.update(text.as_bytes());
+    mac.finalize().into_bytes().into()
+}
+
+/// Splits a record line into its hash and text.
+fn parse_line(line: &str) -> Option<(Hash, &str)> {
+    if let Some(prev) = line.strip_prefix(HEADER_PREFIX) {
+        return unhex(prev).map(|hash| (hash, ""));
+    }
+    let (hash, text) = line.split_once(' ')?;
+    Some((unhex(hash)?, text))
+}
+
+fn hex(hash: &Hash) -> String {
+    hash.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn unhex(s: &str) -> Option<Hash> {
+    if s.len() != 64 {
+        return None;
+    }
+    let mut hash = [0u8; 32];
+    for (i, byte) in hash.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(s.get(2 * i..2 * i + 2)?, 16).ok()?;
+    }
+    Some(hash)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::rolling::{RollingFile, RollingOptions, RotationNaming};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("alumy-audit-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn key() -> AuditKey {
+        AuditKey::new(*b"test audit key")
+    }
+
+    fn write_trail(path: &Path, lines: std::ops::Range<usize>) {
+        let chain = Arc::new(Mutex::new(Chain::resume(path, key()).unwrap()));
+        let options = RollingOptions {
+            path: path.to_path_buf(),
+            max_size: 200,
+            max_files: 10,
+            naming: RotationNaming::Numeric,
+            header: Some(audit_header(chain.clone())),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        let file = RollingFile::open(options, Arc::default()).unwrap();
+        let mut sink = AuditSink::new(file, chain);
+        for i in lines {
+            sink.write_all(format!("user {i} logged in\n").as_bytes()).unwrap();
+        }
+        sink.flush().unwrap();
+    }
+
+    #[test]
+    fn test_chain_across_rotations_and_restarts() {
+        let dir = temp_dir("intact");
+        let path = dir.join("audit.log");
+        write_trail(&path, 0..10);
+        write_trail(&path, 10..20);
+
+        let report = verify(&path, &key()).unwrap();
+        assert!(report.is_intact(), "{:?}", report.broken);
+        assert_eq!(report.records, 20);
+        assert!(report.files.len() > 2, "Should have rotated");
+        assert!(report.last_hash.is_some());
+
+        let forged = verify(&path, &AuditKey::new(*b"other key")).unwrap().broken.unwrap();
+        assert_eq!(forged.line, 2, "A chain computed with another key should not verify");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_detects_edit_and_removal() {
+        let dir = temp_dir("tampered");
+        let path = dir.join("audit.log");
+        write_trail(&path, 0..20);
+
+        let report = verify(&path, &key()).unwrap();
+        let middle = &report.files[1];
+        let original = fs::read_to_string(middle).unwrap();
+
+        fs::write(middle, original.replacen("logged in", "logged out", 1)).unwrap();
+        let broken = verify(&path, &key()).unwrap().broken.unwrap();
+        assert_eq!((&broken.file, broken.line), (middle, 2));
+
+        let mut lines: Vec<_> = original.lines().collect();
+        lines.remove(1);
+        fs::write(middle, lines.join("\n") + "\n").unwrap();
+        let broken = verify(&path, &key()).unwrap().broken.unwrap();
+        assert_eq!((&broken.file, broken.line), (middle, 2));
+
+        fs::remove_file(middle).unwrap();
+        let broken = verify(&path, &key()).unwrap().broken.unwrap();
+        assert_eq!(broken.reason, "chain header does not match the previous record");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
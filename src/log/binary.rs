@@ -0,0 +1,873 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::hash::{Hash, Hasher};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+use std::time::Duration;
+use tracing::callsite::Identifier;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Metadata, Subscriber};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::correlation::event_context;
+use super::rolling::HeaderFn;
+use super::writer::Sink;
+
+/// Starts every file and every reopening of it; resets the decoder's tables.
+const MAGIC: &[u8] = b"\xa1ALB1";
+
+const TAG_CALLSITE: u8 = 1;
+const TAG_STRING: u8 = 2;
+const TAG_EVENT: u8 = 3;
+const TAG_NOTE: u8 = 4;
+const TAG_BASE: u8 = 5;
+
+/// Callsite ID of frames that carry a note from the logger itself.
+const NOTE_ID: u64 = u32::MAX as u64;
+/// Field index of the request ID of the span an event was logged in.
+const REQUEST_ID_INDEX: u64 = u32::MAX as u64;
+
+const VALUE_STR: u8 = 0;
+const VALUE_STR_REF: u8 = 1;
+const VALUE_I64: u8 = 2;
+const VALUE_U64: u8 = 3;
+const VALUE_F64: u8 = 4;
+const VALUE_BOOL: u8 = 5;
+const VALUE_DEBUG: u8 = 6;
+
+/// Strings up to this length are interned once they have been seen twice.
+const MAX_INTERNED_LEN: usize = 256;
+/// Interned strings per file; they are repeated at the top of every new file.
+const MAX_INTERNED: usize = 1024;
+/// Hashes of strings seen once, forgotten when full.
+const MAX_SEEN: usize = 8192;
+
+/// Callsites that have logged, numbered in order of first use.
+#[derive(Default)]
+struct Callsites {
+    ids: HashMap<Identifier, u32>,
+    list: Vec<&'static Metadata<'static>>,
+}
+
+fn callsites() -> &'static RwLock<Callsites> {
+    static CALLSITES: OnceLock<RwLock<Callsites>> = OnceLock::new();
+    CALLSITES.get_or_init(Default::default)
+}
+
+fn callsite_id(metadata: &'static Metadata<'static>) -> u32 {
+    let key = metadata.callsite();
+    if let Some(&id) = callsites().read().unwrap_or_else(|e| e.into_inner()).ids.get(&key) {
+        return id;
+    }
+
+    let mut callsites = callsites().write().unwrap_or_else(|e| e.into_inner());
+    let next = callsites.list.len() as u32;
+    let id = *callsites.ids.entry(key).or_insert(next);
+    if id == next {
+        callsites.list.push(metadata);
+    }
+    id
+}
+
+fn callsite(id: u32) -> Option<&'static Metadata<'static>> {
+    callsites().read().unwrap_or_else(|e| e.into_inner()).list.get(id as usize).copied()
+}
+
+/// Layer that encodes events as compact binary frames for a [`BinarySink`].
+///
+/// A frame holds the callsite ID, the uptime and the typed field values,
+/// followed by the request ID of the enclosing span if there is one. The
+/// sink interns strings and adds the callsite definitions on the writer thread,
+/// where it knows which file the frame ends up in.
+pub(crate) struct BinaryLayer<W> {
+    make_writer: W,
+}
+
+impl<W> BinaryLayer<W> {
+    pub(crate) fn new(make_writer: W) -> Self {
+        Self { make_writer }
+    }
+}
+
+impl<S, W> Layer<S> for BinaryLayer<W>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'a> MakeWriter<'a> + 'static,
+{
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut values = FrameVisitor::default();
+        event.record(&mut values);
+        if let Some(request_id) = event_context(event, &ctx).and_then(|c| c.request_id) {
+            values.count += 1;
+            write_varint(&mut values.out, REQUEST_ID_INDEX);
+            values.out.push(VALUE_STR);
+            write_str(&mut values.out, &request_id);
+        }
+
+        let mut body = Vec::with_capacity(16 + values.out.len());
+        write_varint(&mut body, callsite_id(event.metadata()) as u64);
+        write_varint(&mut body, crate::sys::uptime::uptime_duration().as_micros() as u64);
+        write_varint(&mut body, values.count << 1);
+        body.extend_from_slice(&values.out);
+
+        let mut frame = Vec::with_capacity(4 + body.len());
+        frame.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        frame.extend_from_slice(&body);
+        let _ = self.make_writer.make_writer_for(event.metadata()).write_all(&frame);
+    }
+}
+
+#[derive(Default)]
+struct FrameVisitor {
+    out: Vec<u8>,
+    count: u64,
+}
+
+impl FrameVisitor {
+    fn field(&mut self, field: &Field, tag: u8) {
+        self.count += 1;
+        write_varint(&mut self.out, field.index() as u64);
+        self.out.push(tag);
+    }
+}
+
+impl Visit for FrameVisitor {
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.field(field, VALUE_F64);
+        self.out.extend_from_slice(&value.to_le_bytes());
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.field(field, VALUE_I64);
+        write_varint(&mut self.out, ((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        self.field(field, VALUE_U64);
+        write_varint(&mut self.out, value);
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.field(field, VALUE_BOOL);
+        self.out.push(value as u8);
+    }
+
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.field(field, VALUE_STR);
+        write_str(&mut self.out, value);
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        // Messages are stored as strings so that repeated ones are interned.
+        let tag = if field.name() == "message" { VALUE_STR } else { VALUE_DEBUG };
+        self.field(field, tag);
+        write_str(&mut self.out, &format!("{value:?}"));
+    }
+}
+
+/// What has been written to the current file, shared with its header.
+#[derive(Default)]
+pub(crate) struct Tables {
+    callsites: HashSet<u32>,
+    strings: HashMap<String, u32>,
+    seen: HashSet<u64>,
+    /// Uptime of the last event; events store the difference to it.
+    last_uptime: u64,
+    /// `last_uptime` before the records being written, where a new file starts.
+    base_uptime: u64,
+}
+
+/// Returns the header that starts every binary log file: the magic followed by
+/// all callsites and strings that later records may refer to.
+pub(crate) fn binary_header(tables: Arc<Mutex<Tables>>) -> HeaderFn {
+    Arc::new(move || {
+        let tables = tables.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = MAGIC.to_vec();
+        out.push(TAG_BASE);
+        write_varint(&mut out, tables.base_uptime);
+
+        let mut ids: Vec<_> = tables.callsites.iter().copied().collect();
+        ids.sort_unstable();
+        for id in ids {
+            write_callsite(&mut out, id);
+        }
+
+        let mut strings: Vec<_> = tables.strings.iter().collect();
+        strings.sort_unstable_by_key(|(_, id)| **id);
+        for (s, id) in strings {
+            write_string_def(&mut out, *id, s);
+        }
+        out
+    })
+}
+
+/// Turns frames from a [`BinaryLayer`] into file records, interning strings and
+/// defining each callsite before its first use in a file.
+pub(crate) struct BinarySink<S> {
+    inner: S,
+    tables: Arc<Mutex<Tables>>,
+    input: Vec<u8>,
+    output: Vec<u8>,
+}
+
+impl<S: Sink> BinarySink<S> {
+    pub(crate) fn new(inner: S, tables: Arc<Mutex<Tables>>) -> Self {
+        Self {
+            inner,
+            tables,
+            input: Vec::new(),
+            output: Vec::new(),
+        }
+    }
+
+    fn encode(&mut self, mut body: &[u8]) -> Option<()> {
+        let id = read_varint(&mut body)?;
+        let uptime = read_varint(&mut body)?;
+        if id == NOTE_ID {
+            self.output.push(TAG_NOTE);
+            write_varint(&mut self.output, uptime);
+            write_str(&mut self.output, read_str(&mut body)?);
+            return Some(());
+        }
+        let id = id as u32;
+        let count = read_varint(&mut body)? >> 1;
+
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        if tables.callsites.insert(id) {
+            write_callsite(&mut self.output, id);
+        }
+
+        let delta = uptime.wrapping_sub(tables.last_uptime) as i64;
+        tables.last_uptime = uptime;
+
+        let mut indices = Vec::with_capacity(count as usize);
+        let mut values = Vec::new();
+        for _ in 0..count {
+            indices.push(read_varint(&mut body)?);
+            match *body.first()? {
+                VALUE_STR => {
+                    body = &body[1..];
+                    let s = read_str(&mut body)?;
+                    match intern(&mut tables, s, &mut self.output) {
+                        Some(string_id) => {
+                            values.push(VALUE_STR_REF);
+                            write_varint(&mut values, string_id as u64);
+                        }
+                        None => {
+                            values.push(VALUE_STR);
+                            write_str(&mut values, s);
+                        }
+                    }
+                }
+                _ => {
+                    let start = body;
+                    read_value(&mut body, &[])?;
+                    values.extend_from_slice(&start[..start.len() - body.len()]);
+                }
+            }
+        }
+
+        // Field indices are left out when the values follow the callsite's field order.
+        let in_order = indices.iter().enumerate().all(|(i, &index)| index == i as u64);
+        self.output.push(TAG_EVENT);
+        write_varint(&mut self.output, id as u64);
+        write_varint(&mut self.output, ((delta << 1) ^ (delta >> 63)) as u64);
+        write_varint(&mut self.output, count << 1 | in_order as u64);
+        if in_order {
+            self.output.extend_from_slice(&values);
+        } else {
+            let mut values = values.as_slice();
+            for index in indices {
+                write_varint(&mut self.output, index);
+                let start = values;
+                read_value(&mut values, &[])?;
+                self.output.extend_from_slice(&start[..start.len() - values.len()]);
+            }
+        }
+        Some(())
+    }
+}
+
+/// Returns the ID of `s` if it is, or has now become, an interned string.
+fn intern(tables: &mut Tables, s: &str, defs: &mut Vec<u8>) -> Option<u32> {
+    if let Some(&id) = tables.strings.get(s) {
+        return Some(id);
+    }
+    if s.len() > MAX_INTERNED_LEN || tables.strings.len() >= MAX_INTERNED {
+        return None;
+    }
+
+    let mut hasher = DefaultHasher::new();
+    s.hash(&mut hasher);
+    if tables.seen.len() >= MAX_SEEN {
+        tables.seen.clear();
+    }
+    if tables.seen.insert(hasher.finish()) {
+        return None;
+    }
+
+    let id = tables.strings.len() as u32;
+    tables.strings.insert(s.to_string(), id);
+    write_string_def(defs, id, s);
+    Some(id)
+}
+
+impl<S: Sink> Write for BinarySink<S> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.input.extend_from_slice(buf);
+
+        let mut pos = 0;
+        while let Some(len) = self.input.get(pos..pos + 4).map(|b| u32::from_le_bytes(b.try_into().unwrap()) as usize) {
+            let Some(body) = self.input.get(pos + 4..pos + 4 + len) else {
+                break;
+            };
+            let body = body.to_vec();
+            pos += 4 + len;
+            if self.encode(&body).is_none() {
+                self.input.clear();
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "malformed binary log frame"));
+            }
+        }
+        self.input.drain(..pos);
+
+        let result = self.inner.write_all(&self.output);
+        self.output.clear();
+        let mut tables = self.tables.lock().unwrap_or_else(|e| e.into_inner());
+        tables.base_uptime = tables.last_uptime;
+        result.map(|()| buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+impl<S: Sink> Sink for BinarySink<S> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.inner.sync_data()
+    }
+}
+
+/// Renders frames from a [`BinaryLayer`] as text lines, for the stderr fallback.
+pub(crate) fn frames_to_text(mut buf: &[u8]) -> Vec<u8> {
+    let mut text = Vec::new();
+    while buf.len() >= 4 {
+        let len = u32::from_le_bytes(buf[..4].try_into().unwrap()) as usize;
+        let Some(mut body) = buf.get(4..4 + len) else {
+            break;
+        };
+        buf = &buf[4 + len..];
+
+        let event = (|| {
+            let id = read_varint(&mut body)?;
+            let uptime = read_varint(&mut body)?;
+            if id == NOTE_ID {
+                return Some(note_event(uptime, read_str(&mut body)?));
+            }
+            read_event(&mut body, &Callsite::from(callsite(id as u32)?), uptime, &[])
+        })();
+        if let Some(event) = event {
+            let _ = writeln!(text, "{event}");
+        }
+    }
+    text
+}
+
+/// Encodes a note from the logger itself, such as the recovery record, as a frame.
+pub(crate) fn note(text: &str) -> Vec<u8> {
+    let mut body = Vec::new();
+    write_varint(&mut body, NOTE_ID);
+    write_varint(&mut body, crate::sys::uptime::uptime_duration().as_micros() as u64);
+    write_str(&mut body, text);
+
+    let mut frame = (body.len() as u32).to_le_bytes().to_vec();
+    frame.extend_from_slice(&body);
+    frame
+}
+
+fn note_event(uptime: u64, message: &str) -> BinaryEvent {
+    BinaryEvent {
+        level: Level::WARN,
+        target: "alumy".to_string(),
+        uptime: Duration::from_micros(uptime),
+        message: message.to_string(),
+        fields: Vec::new(),
+        file: None,
+        line: None,
+    }
+}
+
+/// An event read back from a binary log file by [`decode_binary`].
+///
+/// Its [`Display`](fmt::Display) implementation renders the usual text format
+/// with uptime timestamps.
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryEvent {
+    pub level: Level,
+    pub target: String,
+    /// System uptime when the event was logged.
+    pub uptime: Duration,
+    pub message: String,
+    /// Field names and values, rendered as in the text format.
+    pub fields: Vec<(String, String)>,
+    pub file: Option<String>,
+    pub line: Option<u32>,
+}
+
+impl fmt::Display for BinaryEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "[{:>6}.{:03}] {:>5} {}: {}",
+            self.uptime.as_secs(),
+            self.uptime.subsec_millis(),
+            self.level,
+            self.target,
+            self.message
+        )?;
+        for (i, (name, value)) in self.fields.iter().enumerate() {
+            let sep = if i == 0 && self.message.is_empty() { "" } else { " " };
+            write!(f, "{sep}{name}={value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a log file written with [`LogFormat::Binary`](super::LogFormat::Binary).
+///
+/// A record cut short at the end of the file, e.g. by a power loss, is ignored.
+///
+/// # Examples
+///
+/// ```no_run
+/// for event in alumy::log::decode_binary("logs/app.log")? {
+///     println!("{event}");
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn decode_binary(path: impl AsRef<Path>) -> io::Result<Vec<BinaryEvent>> {
+    decode_binary_bytes(&fs::read(path)?)
+}
+
+/// Decodes the contents of a binary log file, e.g. after decrypting it.
+pub fn decode_binary_bytes(mut data: &[u8]) -> io::Result<Vec<BinaryEvent>> {
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, format!("invalid binary log: {what}"));
+    if !data.starts_with(MAGIC) {
+        return Err(invalid("missing header"));
+    }
+
+    let mut callsites: HashMap<u32, Callsite> = HashMap::new();
+    let mut strings: Vec<String> = Vec::new();
+    let mut last_uptime = 0u64;
+    let mut events = Vec::new();
+
+    while let Some(&tag) = data.first() {
+        let mut rest = &data[1..];
+        let complete = match tag {
+            _ if data.starts_with(MAGIC) => {
+                callsites.clear();
+                strings.clear();
+                last_uptime = 0;
+                rest = &data[MAGIC.len()..];
+                Some(())
+            }
+            TAG_CALLSITE => read_callsite(&mut rest).map(|(id, callsite)| {
+                callsites.insert(id, callsite);
+            }),
+            TAG_STRING => (|| {
+                let id = read_varint(&mut rest)? as usize;
+                let s = read_str(&mut rest)?.to_string();
+                strings.resize(strings.len().max(id + 1), String::new());
+                strings[id] = s;
+                Some(())
+            })(),
+            TAG_BASE => read_varint(&mut rest).map(|base| last_uptime = base),
+            TAG_EVENT => {
+                let Some((id, delta)) = read_varint(&mut rest).zip(read_varint(&mut rest)) else {
+                    break;
+                };
+                let callsite = callsites.get(&(id as u32)).ok_or_else(|| invalid("unknown callsite"))?;
+                let uptime = last_uptime.wrapping_add((((delta >> 1) as i64) ^ -((delta & 1) as i64)) as u64);
+                read_event(&mut rest, callsite, uptime, &strings).map(|event| {
+                    last_uptime = uptime;
+                    events.push(event);
+                })
+            }
+            TAG_NOTE => (|| {
+                let uptime = read_varint(&mut rest)?;
+                events.push(note_event(uptime, read_str(&mut rest)?));
+                Some(())
+            })(),
+            _ => return Err(invalid("unknown record")),
+        };
+        if complete.is_none() {
+            // Truncated final record.
+            break;
+        }
+        data = rest;
+    }
+    Ok(events)
+}
+
+/// Callsite metadata as stored in a file.
+struct Callsite {
+    level: Level,
+    target: String,
+    file: Option<String>,
+    line: Option<u32>,
+    fields: Vec<String>,
+}
+
+impl From<&Metadata<'_>> for Callsite {
+    fn from(metadata: &Metadata<'_>) -> Self {
+        Self {
+            level: *metadata.level(),
+            target: metadata.target().to_string(),
+            file: metadata.file().map(str::to_string),
+            line: metadata.line(),
+            fields: metadata.fields().iter().map(|f| f.name().to_string()).collect(),
+        }
+    }
+}
+
+fn write_callsite(out: &mut Vec<u8>, id: u32) {
+    let Some(metadata) = callsite(id) else {
+        return;
+    };
+    out.push(TAG_CALLSITE);
+    write_varint(out, id as u64);
+    out.push(level_code(*metadata.level()));
+    write_str(out, metadata.target());
+    write_str(out, metadata.file().unwrap_or(""));
+    write_varint(out, metadata.line().map_or(0, |line| line as u64 + 1));
+    write_varint(out, metadata.fields().len() as u64);
+    for field in metadata.fields() {
+        write_str(out, field.name());
+    }
+}
+
+fn read_callsite(data: &mut &[u8]) -> Option<(u32, Callsite)> {
+    let id = read_varint(data)? as u32;
+    let level = level_from_code(*data.first()?)?;
+    *data = &data[1..];
+    let target = read_str(data)?.to_string();
+    let file = Some(read_str(data)?).filter(|f| !f.is_empty()).map(str::to_string);
+    let line = read_varint(data)?.checked_sub(1).map(|line| line as u32);
+    let count = read_varint(data)?;
+    let fields = (0..count).map(|_| read_str(data).map(str::to_string)).collect::<Option<_>>()?;
+    Some((
+        id,
+        Callsite {
+            level,
+            target,
+            file,
+            line,
+            fields,
+        },
+    ))
+}
+
+fn write_string_def(out: &mut Vec<u8>, id: u32, s: &str) {
+    out.push(TAG_STRING);
+    write_varint(out, id as u64);
+    write_str(out, s);
+}
+
+fn read_event(data: &mut &[u8], callsite: &Callsite, uptime: u64, strings: &[String]) -> Option<BinaryEvent> {
+    let mut event = BinaryEvent {
+        level: callsite.level,
+        target: callsite.target.clone(),
+        uptime: Duration::from_micros(uptime),
+        message: String::new(),
+        fields: Vec::new(),
+        file: callsite.file.clone(),
+        line: callsite.line,
+    };
+
+    let count = read_varint(data)?;
+    let in_order = count & 1 == 1;
+    for i in 0..count >> 1 {
+        let index = if in_order { i } else { read_varint(data)? };
+        let name = match index {
+            REQUEST_ID_INDEX => "request_id",
+            _ => callsite.fields.get(index as usize).map_or("?", String::as_str),
+        };
+        let (value, is_str) = read_value(data, strings)?;
+        match name {
+            "message" => event.message = value,
+            // Records forwarded from the `log` crate carry their real location in fields.
+            "log.target" => event.target = value,
+            "log.file" => event.file = Some(value),
+            "log.line" => event.line = value.parse().ok(),
+            "log.module_path" => {}
+            _ if is_str => event.fields.push((name.to_string(), format!("{value:?}"))),
+            _ => event.fields.push((name.to_string(), value)),
+        }
+    }
+    Some(event)
+}
+
+/// Reads a value, returning it as text and whether it was a string.
+fn read_value(data: &mut &[u8], strings: &[String]) -> Option<(String, bool)> {
+    let tag = *data.first()?;
+    *data = &data[1..];
+    Some(match tag {
+        VALUE_STR => (read_str(data)?.to_string(), true),
+        VALUE_STR_REF => (strings.get(read_varint(data)? as usize)?.clone(), true),
+        VALUE_I64 => {
+            let v = read_varint(data)?;
+            ((((v >> 1) as i64) ^ -((v & 1) as i64)).to_string(), false)
+        }
+        VALUE_U64 => (read_varint(data)?.to_string(), false),
+        VALUE_F64 => {
+            let bytes = data.get(..8)?;
+            *data = &data[8..];
+            (f64::from_le_bytes(bytes.try_into().ok()?).to_string(), false)
+        }
+        VALUE_BOOL => {
+            let v = *data.first()? != 0;
+            *data = &data[1..];
+            (v.to_string(), false)
+        }
+        VALUE_DEBUG => (read_str(data)?.to_string(), false),
+        _ => return None,
+    })
+}
+
+fn level_code(level: Level) -> u8 {
+    match level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+fn level_from_code(code: u8) -> Option<Level> {
+    Some(match code {
+        1 => Level::ERROR,
+        2 => Level::WARN,
+        3 => Level::INFO,
+        4 => Level::DEBUG,
+        5 => Level::TRACE,
+        _ => return None,
+    })
+}
+
+fn write_varint(out: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        out.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &mut &[u8]) -> Option<u64> {
+    let mut value = 0u64;
+    for (i, &byte) in data.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            *data = &data[i + 1..];
+            return Some(value);
+        }
+    }
+    None
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len() as u64);
+    out.extend_from_slice(s.as_bytes());
+}
+
+fn read_str<'a>(data: &mut &'a [u8]) -> Option<&'a str> {
+    let len = read_varint(data)? as usize;
+    let bytes = data.get(..len)?;
+    *data = &data[len..];
+    std::str::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::correlation::{request_span, CorrelationLayer};
+    use tracing_subscriber::prelude::*;
+
+    /// In-memory sink standing in for a file.
+    #[derive(Clone, Default)]
+    struct Memory(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Memory {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Sink for Memory {}
+
+    /// Captures frames from the layer, as the non-blocking writer would.
+    impl<'a> MakeWriter<'a> for Memory {
+        type Writer = Memory;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn log_frames(f: impl FnOnce()) -> Vec<u8> {
+        let frames = Memory::default();
+        let subscriber = tracing_subscriber::registry()
+            .with(CorrelationLayer)
+            .with(BinaryLayer::new(frames.clone()));
+        tracing::subscriber::with_default(subscriber, f);
+        let bytes = frames.0.lock().unwrap().clone();
+        bytes
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u32::MAX as u64, u64::MAX] {
+            let mut out = Vec::new();
+            write_varint(&mut out, value);
+            let mut data = out.as_slice();
+            assert_eq!(read_varint(&mut data), Some(value));
+            assert!(data.is_empty());
+        }
+    }
+
+    #[test]
+    fn test_encode_decode() {
+        let frames = log_frames(|| {
+            for i in 0..3 {
+                tracing::info!(target: "app", port = "/dev/ttyS0", retries = i, ok = true, "device ready");
+            }
+            tracing::warn!(target: "app", delta = -5i64, ratio = 0.5, "clock adjusted");
+        });
+
+        let tables = Arc::new(Mutex::new(Tables::default()));
+        let file = Memory::default();
+        file.clone().write_all(&binary_header(tables.clone())()).unwrap();
+        let mut sink = BinarySink::new(file.clone(), tables);
+        // Frames may be split across writes.
+        let (a, b) = frames.split_at(7);
+        sink.write_all(a).unwrap();
+        sink.write_all(b).unwrap();
+
+        let events = decode_binary_bytes(&file.0.lock().unwrap()).unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[0].level, Level::INFO);
+        assert_eq!(events[0].target, "app");
+        assert_eq!(events[0].message, "device ready");
+        assert_eq!(
+            events[2].fields,
+            [
+                ("port".to_string(), "\"/dev/ttyS0\"".to_string()),
+                ("retries".to_string(), "2".to_string()),
+                ("ok".to_string(), "true".to_string()),
+            ]
+        );
+        assert_eq!(events[3].fields[0], ("delta".to_string(), "-5".to_string()));
+        assert_eq!(events[3].fields[1], ("ratio".to_string(), "0.5".to_string()));
+        assert!(events[0].line.is_some());
+
+        let text = events[3].to_string();
+        assert!(text.starts_with('['), "{text}");
+        assert!(text.ends_with("]  WARN app: clock adjusted delta=-5 ratio=0.5"), "{text}");
+    }
+
+    #[test]
+    fn test_request_id() {
+        let frames = log_frames(|| {
+            tracing::info!(target: "app", "starting");
+            let span = request_span("req-1", None);
+            let _span = span.enter();
+            tracing::info!(target: "app", user = "ann", "handled");
+        });
+
+        let tables = Arc::new(Mutex::new(Tables::default()));
+        let file = Memory::default();
+        file.clone().write_all(&binary_header(tables.clone())()).unwrap();
+        BinarySink::new(file.clone(), tables).write_all(&frames).unwrap();
+
+        let events = decode_binary_bytes(&file.0.lock().unwrap()).unwrap();
+        assert!(events[0].fields.is_empty());
+        assert_eq!(
+            events[1].fields,
+            [
+                ("user".to_string(), "\"ann\"".to_string()),
+                ("request_id".to_string(), "\"req-1\"".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_header_redefines_after_rotation() {
+        let frames = log_frames(|| {
+            tracing::info!(target: "app", "repeated message");
+            tracing::info!(target: "app", "repeated message");
+        });
+
+        let tables = Arc::new(Mutex::new(Tables::default()));
+        let header = binary_header(tables.clone());
+        let first = Memory::default();
+        first.clone().write_all(&header()).unwrap();
+        let mut sink = BinarySink::new(first.clone(), tables);
+        sink.write_all(&frames).unwrap();
+
+        // A new file starts with every definition the next records may use.
+        let second = Memory::default();
+        sink.inner = second.clone();
+        second.clone().write_all(&header()).unwrap();
+        sink.write_all(&frames).unwrap();
+
+        let first = first.0.lock().unwrap().clone();
+        let second = second.0.lock().unwrap().clone();
+        assert_eq!(decode_binary_bytes(&first).unwrap().len(), 2);
+        let events = decode_binary_bytes(&second).unwrap();
+        assert_eq!(events.len(), 2);
+        assert!(events.iter().all(|e| e.message == "repeated message"));
+        let occurrences = |bytes: &[u8]| bytes.windows(16).filter(|w| w == b"repeated message").count();
+        assert_eq!(occurrences(&first), 2, "Inlined once, then interned");
+        assert_eq!(occurrences(&second), 1, "Interned string repeated in the header only");
+    }
+
+    #[test]
+    fn test_truncated_tail_is_ignored() {
+        let frames = log_frames(|| {
+            tracing::info!("one");
+            tracing::info!("two");
+        });
+        let tables = Arc::new(Mutex::new(Tables::default()));
+        let file = Memory::default();
+        file.clone().write_all(&binary_header(tables.clone())()).unwrap();
+        let mut sink = BinarySink::new(file.clone(), tables);
+        sink.write_all(&frames).unwrap();
+        sink.write_all(&note("recovered")).unwrap();
+
+        let mut bytes = file.0.lock().unwrap().clone();
+        assert_eq!(decode_binary_bytes(&bytes).unwrap().len(), 3);
+        bytes.truncate(bytes.len() - 3);
+        let events = decode_binary_bytes(&bytes).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[1].message, "two");
+        assert!(decode_binary_bytes(b"plain text\n").is_err());
+    }
+
+    #[test]
+    fn test_frames_to_text() {
+        let frames = log_frames(|| tracing::error!(target: "app", code = 7, "failed"));
+        let text = String::from_utf8(frames_to_text(&frames)).unwrap();
+        assert!(text.ends_with(" ERROR app: failed code=7\n"), "{text}");
+    }
+}
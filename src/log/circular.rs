@@ -0,0 +1,460 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::Path;
+
+use super::writer::Sink;
+
+/// Two header slots, written alternately so that one of them is always intact.
+const HEADER_SIZE: u64 = 128;
+const SLOT_SIZE: usize = 64;
+const SLOT_LEN: usize = 44;
+const HEADER_MAGIC: &[u8; 4] = b"ALCB";
+const VERSION: u32 = 1;
+
+/// Record framing: magic, payload length, sequence number and CRC-32 of sequence and payload.
+const RECORD_MAGIC: [u8; 4] = [0xa1, 0x0c, 0xb7, 0x5e];
+const RECORD_HEADER: u64 = 20;
+
+/// Smallest data area accepted, so that ordinary records always fit.
+const MIN_CAPACITY: u64 = 4096;
+
+/// Smallest file size accepted for [`FileMode::Circular`](super::FileMode::Circular).
+pub(crate) const MIN_SIZE: u64 = HEADER_SIZE + MIN_CAPACITY;
+
+/// Pending records are written out once they exceed this size, even without a flush.
+const MAX_PENDING: usize = 64 * 1024;
+
+/// A flush updates the header once this many bytes of records were written since
+/// the last update; opening the file walks forward over the records after it.
+const HEADER_INTERVAL: u64 = 64 * 1024;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Header {
+    capacity: u64,
+    offset: u64,
+    next_seq: u64,
+    generation: u64,
+}
+
+impl Header {
+    fn encode(&self) -> [u8; SLOT_SIZE] {
+        let mut slot = [0u8; SLOT_SIZE];
+        slot[0..4].copy_from_slice(HEADER_MAGIC);
+        slot[4..8].copy_from_slice(&VERSION.to_le_bytes());
+        slot[8..16].copy_from_slice(&self.capacity.to_le_bytes());
+        slot[16..24].copy_from_slice(&self.offset.to_le_bytes());
+        slot[24..32].copy_from_slice(&self.next_seq.to_le_bytes());
+        slot[32..40].copy_from_slice(&self.generation.to_le_bytes());
+        let crc = crc32(&slot[..40]);
+        slot[40..SLOT_LEN].copy_from_slice(&crc.to_le_bytes());
+        slot
+    }
+
+    fn decode(slot: &[u8]) -> Option<Self> {
+        if &slot[0..4] != HEADER_MAGIC
+            || slot[4..8] != VERSION.to_le_bytes()
+            || slot[40..SLOT_LEN] != crc32(&slot[..40]).to_le_bytes()
+        {
+            return None;
+        }
+        Some(Self {
+            capacity: le_u64(&slot[8..16]),
+            offset: le_u64(&slot[16..24]),
+            next_seq: le_u64(&slot[24..32]),
+            generation: le_u64(&slot[32..40]),
+        })
+    }
+
+    /// Returns the newest valid slot of a file header.
+    fn newest(header: &[u8]) -> Option<Self> {
+        let first = Self::decode(&header[..SLOT_SIZE]);
+        let second = Self::decode(&header[SLOT_SIZE..2 * SLOT_SIZE]);
+        match (first, second) {
+            (Some(a), Some(b)) => Some(if a.generation >= b.generation { a } else { b }),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+/// Fixed-size log file used as a circular buffer, for flash-constrained devices.
+///
+/// The file is preallocated once and then overwritten in place: it never grows
+/// and is never renamed. Each record is framed with a sequence number and a
+/// CRC, so a record torn by a power cut is simply skipped by [`read_circular`].
+/// The write position lives in two alternating header slots, updated when the
+/// buffer wraps, every [`HEADER_INTERVAL`] bytes and when the file is synced or
+/// closed, and is recovered by walking the records written after the last
+/// header update. Writing the header less often spares the flash its wear.
+pub(crate) struct CircularFile {
+    file: File,
+    header: Header,
+    pending: Vec<u8>,
+    pending_start: u64,
+    header_dirty: bool,
+    /// Bytes of records written since the last header update.
+    since_header: u64,
+}
+
+impl CircularFile {
+    pub(crate) fn open(path: impl AsRef<Path>, size: u64) -> io::Result<Self> {
+        let path = path.as_ref();
+        let capacity = size.saturating_sub(HEADER_SIZE).max(MIN_CAPACITY);
+        let total = HEADER_SIZE + capacity;
+
+        let mut file = OpenOptions::new().read(true).write(true).create(true).truncate(false).open(path)?;
+        let len = file.metadata()?.len();
+
+        let existing = if len == total {
+            let mut header = [0u8; HEADER_SIZE as usize];
+            file.read_exact(&mut header)?;
+            Header::newest(&header).filter(|h| h.capacity == capacity && h.offset <= capacity)
+        } else {
+            None
+        };
+
+        let header = match existing {
+            Some(header) => recover(&mut file, header)?,
+            None => {
+                if len > 0 {
+                    eprintln!(
+                        "alumy: reinitializing circular log file {} ({len} bytes, expected {total})",
+                        path.display()
+                    );
+                }
+                preallocate(&mut file, total)?;
+                Header {
+                    capacity,
+                    offset: 0,
+                    next_seq: 0,
+                    generation: 0,
+                }
+            }
+        };
+
+        let mut circular = Self {
+            file,
+            header,
+            pending: Vec::with_capacity(MAX_PENDING),
+            pending_start: header.offset,
+            header_dirty: true,
+            since_header: 0,
+        };
+        circular.write_header()?;
+        Ok(circular)
+    }
+
+    fn write_pending(&mut self) -> io::Result<()> {
+        if self.pending.is_empty() {
+            return Ok(());
+        }
+        self.file.seek(SeekFrom::Start(HEADER_SIZE + self.pending_start))?;
+        self.file.write_all(&self.pending)?;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn write_header(&mut self) -> io::Result<()> {
+        if !self.header_dirty {
+            return Ok(());
+        }
+        self.header.generation += 1;
+        let slot = (self.header.generation % 2) * SLOT_SIZE as u64;
+        self.file.seek(SeekFrom::Start(slot))?;
+        self.file.write_all(&self.header.encode())?;
+        self.header_dirty = false;
+        self.since_header = 0;
+        Ok(())
+    }
+}
+
+impl Write for CircularFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Oversized records are cut so they still fit in the buffer.
+        let payload = &buf[..buf.len().min((self.header.capacity - RECORD_HEADER) as usize)];
+        let size = RECORD_HEADER + payload.len() as u64;
+
+        if self.header.offset + size > self.header.capacity {
+            // A header more than a lap behind would point at overwritten records.
+            self.write_pending()?;
+            self.header.offset = 0;
+            self.header_dirty = true;
+            self.write_header()?;
+        }
+        if self.pending.is_empty() {
+            self.pending_start = self.header.offset;
+        }
+
+        encode_record(&mut self.pending, self.header.next_seq, payload);
+        self.header.offset += size;
+        self.header.next_seq += 1;
+        self.header_dirty = true;
+        self.since_header += size;
+
+        if self.pending.len() >= MAX_PENDING {
+            self.write_pending()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        if self.since_header >= HEADER_INTERVAL {
+            self.write_header()?;
+        }
+        Ok(())
+    }
+}
+
+impl Sink for CircularFile {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.write_pending()?;
+        self.write_header()?;
+        self.file.sync_data()
+    }
+}
+
+impl Drop for CircularFile {
+    fn drop(&mut self) {
+        let _ = self.write_pending().and_then(|()| self.write_header());
+    }
+}
+
+/// Reads the records of a circular log file, oldest first.
+///
+/// Records that were torn by a power cut or partly overwritten are skipped.
+/// A trailing newline is removed from each record.
+///
+/// # Examples
+///
+/// ```no_run
+/// for line in alumy::log::read_circular("/data/log/app.log")? {
+///     println!("{line}");
+/// }
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn read_circular(path: impl AsRef<Path>) -> io::Result<Vec<String>> {
+    let bytes = fs::read(path)?;
+    let header = bytes
+        .get(..HEADER_SIZE as usize)
+        .and_then(Header::newest)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "not a circular log file"))?;
+    let end = (HEADER_SIZE + header.capacity).min(bytes.len() as u64) as usize;
+    let data = &bytes[HEADER_SIZE as usize..end];
+
+    let mut records = Vec::new();
+    let mut pos = 0;
+    while pos + RECORD_HEADER as usize <= data.len() {
+        match decode_record(data, pos) {
+            Some((seq, payload)) => {
+                records.push((seq, payload));
+                pos += RECORD_HEADER as usize + payload.len();
+            }
+            None => pos += 1,
+        }
+    }
+    records.sort_by_key(|(seq, _)| *seq);
+
+    Ok(records
+        .into_iter()
+        .map(|(_, payload)| {
+            let payload = payload.strip_suffix(b"\n").unwrap_or(payload);
+            String::from_utf8_lossy(payload).into_owned()
+        })
+        .collect())
+}
+
+/// Advances past records written after the header was last updated.
+fn recover(file: &mut File, mut header: Header) -> io::Result<Header> {
+    let mut data = vec![0u8; header.capacity as usize];
+    file.seek(SeekFrom::Start(HEADER_SIZE))?;
+    file.read_exact(&mut data)?;
+
+    loop {
+        let next = [header.offset, 0]
+            .into_iter()
+            .find_map(|pos| match decode_record(&data, pos as usize) {
+                Some((seq, payload)) if seq == header.next_seq => Some(pos + RECORD_HEADER + payload.len() as u64),
+                _ => None,
+            });
+        match next {
+            Some(offset) => {
+                header.offset = offset;
+                header.next_seq += 1;
+            }
+            None => return Ok(header),
+        }
+    }
+}
+
+/// Sizes the file and writes zeros over it, so that its blocks are allocated up front.
+fn preallocate(file: &mut File, total: u64) -> io::Result<()> {
+    let zeros = vec![0u8; 64 * 1024];
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    let mut remaining = total;
+    while remaining > 0 {
+        let n = remaining.min(zeros.len() as u64) as usize;
+        file.write_all(&zeros[..n])?;
+        remaining -= n as u64;
+    }
+    file.sync_all()
+}
+
+fn encode_record(out: &mut Vec<u8>, seq: u64, payload: &[u8]) {
+    let mut checked = Vec::with_capacity(8 + payload.len());
+    checked.extend_from_slice(&seq.to_le_bytes());
+    checked.extend_from_slice(payload);
+
+    out.extend_from_slice(&RECORD_MAGIC);
+    out.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    out.extend_from_slice(&seq.to_le_bytes());
+    out.extend_from_slice(&crc32(&checked).to_le_bytes());
+    out.extend_from_slice(payload);
+}
+
+/// Decodes the record at `pos`, returning its sequence number and payload if it is intact.
+fn decode_record(data: &[u8], pos: usize) -> Option<(u64, &[u8])> {
+    let header = data.get(pos..pos + RECORD_HEADER as usize)?;
+    if header[0..4] != RECORD_MAGIC {
+        return None;
+    }
+    let len = u32::from_le_bytes(header[4..8].try_into().ok()?) as usize;
+    let seq = le_u64(&header[8..16]);
+    let crc = u32::from_le_bytes(header[16..20].try_into().ok()?);
+
+    let start = pos + RECORD_HEADER as usize;
+    let payload = data.get(start..start.checked_add(len)?)?;
+    let mut checked = Vec::with_capacity(8 + len);
+    checked.extend_from_slice(&header[8..16]);
+    checked.extend_from_slice(payload);
+    (crc32(&checked) == crc).then_some((seq, payload))
+}
+
+fn le_u64(bytes: &[u8]) -> u64 {
+    u64::from_le_bytes(bytes.try_into().unwrap_or_default())
+}
+
+/// CRC-32 (IEEE 802.3).
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn temp_file(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("alumy-circular-{name}-{}.log", std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn test_crc32() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+    }
+
+    #[test]
+    fn test_wraps_in_place() {
+        let path = temp_file("wrap");
+        let size = 8192;
+        {
+            let mut file = CircularFile::open(&path, size).unwrap();
+            for i in 0..1000 {
+                file.write_all(format!("record {i:04} with some padding text\n").as_bytes()).unwrap();
+                if i % 10 == 0 {
+                    file.flush().unwrap();
+                }
+            }
+        }
+        assert_eq!(fs::metadata(&path).unwrap().len(), size);
+
+        let records = read_circular(&path).unwrap();
+        assert!(records.len() > 100 && records.len() < 1000);
+        assert_eq!(records.last().unwrap(), "record 0999 with some padding text");
+        let first: usize = records[0][7..11].parse().unwrap();
+        for (i, record) in records.iter().enumerate() {
+            assert_eq!(record, &format!("record {:04} with some padding text", first + i));
+        }
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_recovers_after_stale_header() {
+        let path = temp_file("stale");
+        {
+            let mut file = CircularFile::open(&path, 8192).unwrap();
+            file.write_all(b"first\n").unwrap();
+            file.flush().unwrap();
+            // Records reach the file, but the header update is lost.
+            file.write_all(b"second\n").unwrap();
+            file.write_pending().unwrap();
+            std::mem::forget(file);
+        }
+        {
+            let mut file = CircularFile::open(&path, 8192).unwrap();
+            file.write_all(b"third\n").unwrap();
+        }
+        assert_eq!(read_circular(&path).unwrap(), ["first", "second", "third"]);
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_header_is_updated_sparingly() {
+        let path = temp_file("sparing");
+        {
+            let mut file = CircularFile::open(&path, 256 * 1024).unwrap();
+            for i in 0..1000 {
+                file.write_all(format!("record {i:04}\n").as_bytes()).unwrap();
+                file.flush().unwrap();
+            }
+            assert_eq!(file.header.generation, 1, "Only opening the file should write the header");
+            std::mem::forget(file);
+        }
+        {
+            let mut file = CircularFile::open(&path, 256 * 1024).unwrap();
+            assert_eq!(file.header.next_seq, 1000);
+            file.write_all(b"after crash\n").unwrap();
+        }
+        let records = read_circular(&path).unwrap();
+        assert_eq!(records.len(), 1001);
+        assert_eq!(records[999], "record 0999");
+        assert_eq!(records[1000], "after crash");
+
+        let _ = fs::remove_file(&path);
+    }
+
+    #[test]
+    fn test_torn_record_is_skipped() {
+        let path = temp_file("torn");
+        {
+            let mut file = CircularFile::open(&path, 8192).unwrap();
+            for line in ["one\n", "two\n", "three\n"] {
+                file.write_all(line.as_bytes()).unwrap();
+            }
+        }
+        // Corrupt the payload of the last record, as if it was cut by a power loss.
+        let mut bytes = fs::read(&path).unwrap();
+        let pos = HEADER_SIZE as usize + 2 * RECORD_HEADER as usize + 8 + RECORD_HEADER as usize + 2;
+        bytes[pos] = b'X';
+        fs::write(&path, bytes).unwrap();
+
+        assert_eq!(read_circular(&path).unwrap(), ["one", "two"]);
+        let mut file = CircularFile::open(&path, 8192).unwrap();
+        file.write_all(b"four\n").unwrap();
+        drop(file);
+        assert_eq!(read_circular(&path).unwrap(), ["one", "two", "four"]);
+
+        let _ = fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,338 @@
+//! Settings layered under the builder: environment variables and the config file.
+//!
+//! The config file holds one `key = value` setting per line, using the
+//! [`LogConfig`] field names. Blank lines and lines starting with `#` are
+//! ignored, and values may be quoted:
+//!
+//! ```text
+//! # /etc/my-app/my-app.conf
+//! level = debug
+//! filter = "info,my_app::net=trace"
+//! file = /var/log/my-app/my-app.log
+//! max_size = 10M
+//! max_files = 5
+//! format = logfmt
+//! ```
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+use super::file::FileMode;
+use super::format::LogFormat;
+use super::log_init::{logger_reload, LogConfig};
+use super::rolling::RotationNaming;
+
+/// Returns the effective configuration of `config`.
+///
+/// Each setting comes from the first of: an explicit builder call, the
+/// environment (if enabled), the config file, and the level given to
+/// [`LogConfig::new`] or the built-in default.
+pub(crate) fn resolve(config: &LogConfig) -> Result<LogConfig> {
+    let mut explicit = config.clone();
+    // The level given to `new` is the default that every other source overrides.
+    let default_level = explicit.level.take();
+
+    let env = match explicit.name.as_deref() {
+        Some(name) if config.env_overrides.unwrap_or(false) => from_env(name)?,
+        _ => LogConfig::default(),
+    };
+    let file = match explicit.config_file.as_deref() {
+        Some(path) => load(Path::new(path))?,
+        None => LogConfig::default(),
+    };
+
+    let from_builder = explicit.file.is_some();
+    let mut resolved = overlay(overlay(explicit, env), file);
+    resolved.level = resolved.level.or(default_level);
+
+    // A file named outside the builder rolls with the default policy unless configured.
+    if !from_builder && resolved.file.is_some() {
+        resolved.max_size.get_or_insert_with(|| "1M".to_string());
+        resolved.max_files.get_or_insert(5);
+    }
+    Ok(resolved)
+}
+
+/// How often a watched config file is checked for changes.
+const WATCH_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Stops the thread started by [`watch`] when dropped.
+pub(crate) struct Watch {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Reloads the logger from `base` whenever the config file at `path` changes,
+/// until the returned [`Watch`] is dropped.
+pub(crate) fn watch(base: LogConfig, path: PathBuf) -> Option<Watch> {
+    let stamp = |path: &Path| fs::metadata(path).ok().map(|m| (m.modified().ok(), m.len()));
+    let mut last: Option<(Option<SystemTime>, u64)> = stamp(&path);
+
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = stop.clone();
+    let watched = path.clone();
+    let spawned = thread::Builder::new().name("alumy-log-watch".into()).spawn(move || loop {
+        thread::sleep(WATCH_INTERVAL);
+        if stopped.load(Ordering::Relaxed) {
+            break;
+        }
+
+        let current = stamp(&watched);
+        // A file that is being replaced may briefly be missing; keep the current config.
+        if current.is_none() || current == last {
+            continue;
+        }
+        last = current;
+
+        match logger_reload(&base) {
+            Ok(()) => tracing::info!(target: "alumy", "Reloaded log config from {}", watched.display()),
+            Err(e) => tracing::error!(target: "alumy", "Keeping the previous log config: {e:#}"),
+        }
+    });
+    match spawned {
+        Ok(_) => Some(Watch { stop }),
+        Err(e) => {
+            eprintln!("Failed to watch log config {}: {e}", path.display());
+            None
+        }
+    }
+}
+
+/// Returns the prefix of the environment variables for the app `name`: `my-app` becomes `MY_APP`.
+pub(crate) fn env_prefix(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+        .collect()
+}
+
+/// Reads `<NAME>_LOG` (or `RUST_LOG`), `<NAME>_LOG_FILE` and `<NAME>_LOG_FORMAT`.
+fn from_env(name: &str) -> Result<LogConfig> {
+    let prefix = env_prefix(name);
+    let var = |key: &str| std::env::var(key).ok().filter(|v| !v.trim().is_empty());
+
+    let format = var(&format!("{prefix}_LOG_FORMAT"))
+        .map(|v| parse_format(&v).with_context(|| format!("Invalid {prefix}_LOG_FORMAT")))
+        .transpose()?;
+
+    Ok(LogConfig {
+        filter: var(&format!("{prefix}_LOG")).or_else(|| var("RUST_LOG")),
+        file: var(&format!("{prefix}_LOG_FILE")),
+        format,
+        ..Default::default()
+    })
+}
+
+/// Reads the config file at `path`.
+pub(crate) fn load(path: &Path) -> Result<LogConfig> {
+    let text = fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    parse(&text).with_context(|| format!("Invalid config file {}", path.display()))
+}
+
+/// Parses the `key = value` lines of a config file.
+pub(crate) fn parse(text: &str) -> Result<LogConfig> {
+    let mut config = LogConfig::default();
+
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (key, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow!("line {}: expected `key = value`", i + 1))?;
+        let key = key.trim();
+        let value = value.trim();
+        let value = value
+            .strip_prefix('"')
+            .and_then(|v| v.strip_suffix('"'))
+            .unwrap_or(value)
+            .to_string();
+
+        set(&mut config, key, value).with_context(|| format!("line {}", i + 1))?;
+    }
+    Ok(config)
+}
+
+fn set(config: &mut LogConfig, key: &str, value: String) -> Result<()> {
+    match key {
+        "level" => config.level = Some(value),
+        "filter" => config.filter = Some(value),
+        "file" => config.file = Some(value),
+        "max_size" => {
+            crate::fs::filesize::parse_size(&value).ok_or_else(|| anyhow!("invalid size `{value}`"))?;
+            config.max_size = Some(value);
+        }
+        "max_files" => config.max_files = Some(value.parse().map_err(|_| anyhow!("invalid file count `{value}`"))?),
+        "format" => config.format = Some(parse_format(&value)?),
+        "file_mode" => config.file_mode = Some(parse_file_mode(&value)?),
+        "rotation_naming" => config.rotation_naming = Some(parse_rotation_naming(&value)?),
+        "time_format" => config.time_format = Some(value),
+        "version" => config.version = Some(value),
+        "ansi" => config.ansi = Some(parse_bool(&value)?),
+        "display_target" => config.display_target = Some(parse_bool(&value)?),
+        "display_level" => config.display_level = Some(parse_bool(&value)?),
+        "display_time" => config.display_time = Some(parse_bool(&value)?),
+        "display_thread_name" => config.display_thread_name = Some(parse_bool(&value)?),
+        "display_thread_id" => config.display_thread_id = Some(parse_bool(&value)?),
+        "display_identity" => config.display_identity = Some(parse_bool(&value)?),
+        "display_header" => config.display_header = Some(parse_bool(&value)?),
+        "log_bridge" => config.log_bridge = Some(parse_bool(&value)?),
+        _ => bail!("unknown setting `{key}`"),
+    }
+    Ok(())
+}
+
+fn parse_bool(value: &str) -> Result<bool> {
+    match value.to_ascii_lowercase().as_str() {
+        "true" | "yes" | "on" | "1" => Ok(true),
+        "false" | "no" | "off" | "0" => Ok(false),
+        _ => bail!("invalid boolean `{value}`"),
+    }
+}
+
+fn parse_format(value: &str) -> Result<LogFormat> {
+    match value.to_ascii_lowercase().as_str() {
+        "text" => Ok(LogFormat::Text),
+        "logfmt" => Ok(LogFormat::Logfmt),
+        "binary" => Ok(LogFormat::Binary),
+        _ => bail!("unknown log format `{value}`, expected text, logfmt or binary"),
+    }
+}
+
+fn parse_file_mode(value: &str) -> Result<FileMode> {
+    match value.to_ascii_lowercase().as_str() {
+        "rolling" => Ok(FileMode::Rolling),
+        "reopen" => Ok(FileMode::Reopen),
+        "shared" => Ok(FileMode::Shared),
+        "circular" => Ok(FileMode::Circular),
+        _ => bail!("unknown file mode `{value}`, expected rolling, reopen, shared or circular"),
+    }
+}
+
+fn parse_rotation_naming(value: &str) -> Result<RotationNaming> {
+    match value.to_ascii_lowercase().as_str() {
+        "numeric" => Ok(RotationNaming::Numeric),
+        "timestamp" => Ok(RotationNaming::Timestamp),
+        "sequence" => Ok(RotationNaming::Sequence),
+        _ => bail!("unknown rotation naming `{value}`, expected numeric, timestamp or sequence"),
+    }
+}
+
+/// Fills the settings missing from `upper` with those of `lower`.
+fn overlay(upper: LogConfig, lower: LogConfig) -> LogConfig {
+    LogConfig {
+        name: upper.name.or(lower.name),
+        file: upper.file.or(lower.file),
+        level: upper.level.or(lower.level),
+        max_size: upper.max_size.or(lower.max_size),
+        max_files: upper.max_files.or(lower.max_files),
+        filter: upper.filter.or(lower.filter),
+        ansi: upper.ansi.or(lower.ansi),
+        display_target: upper.display_target.or(lower.display_target),
+        display_level: upper.display_level.or(lower.display_level),
+        display_time: upper.display_time.or(lower.display_time),
+        display_thread_name: upper.display_thread_name.or(lower.display_thread_name),
+        display_thread_id: upper.display_thread_id.or(lower.display_thread_id),
+        time_format: upper.time_format.or(lower.time_format),
+        flush_policy: upper.flush_policy.or(lower.flush_policy),
+        file_mode: upper.file_mode.or(lower.file_mode),
+        log_bridge: upper.log_bridge.or(lower.log_bridge),
+        version: upper.version.or(lower.version),
+        display_identity: upper.display_identity.or(lower.display_identity),
+        display_header: upper.display_header.or(lower.display_header),
+        header_hook: upper.header_hook.or(lower.header_hook),
+        durability: upper.durability.or(lower.durability),
+        format: upper.format.or(lower.format),
+        rotation_naming: upper.rotation_naming.or(lower.rotation_naming),
+        audit_target: upper.audit_target.or(lower.audit_target),
+        audit_file: upper.audit_file.or(lower.audit_file),
+        audit_key: upper.audit_key.or(lower.audit_key),
+        env_overrides: upper.env_overrides.or(lower.env_overrides),
+        config_file: upper.config_file.or(lower.config_file),
+        watch_config: upper.watch_config.or(lower.watch_config),
+        theme: upper.theme.or(lower.theme),
+        clock_watch: upper.clock_watch.or(lower.clock_watch),
+        #[cfg(feature = "otlp")]
+        otlp: upper.otlp.or(lower.otlp),
+        #[cfg(feature = "encryption")]
+        encryption: upper.encryption.or(lower.encryption),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let config = parse(
+            "# comment\n\nlevel = debug\nfilter = \"info,app=trace\"\nmax_files=3\nformat = Logfmt\ndisplay_target = yes\n",
+        )
+        .unwrap();
+        assert_eq!(config.level.as_deref(), Some("debug"));
+        assert_eq!(config.filter.as_deref(), Some("info,app=trace"));
+        assert_eq!(config.max_files, Some(3));
+        assert_eq!(config.format, Some(LogFormat::Logfmt));
+        assert_eq!(config.display_target, Some(true));
+
+        let err = parse("level = info\ncolour = red\n").unwrap_err();
+        assert_eq!(format!("{err:#}"), "line 2: unknown setting `colour`");
+        assert!(parse("max_size = lots").is_err());
+        assert!(parse("just a line").is_err());
+    }
+
+    #[test]
+    fn test_env_prefix() {
+        assert_eq!(env_prefix("my-app"), "MY_APP");
+        assert_eq!(env_prefix("svc.v2"), "SVC_V2");
+    }
+
+    #[test]
+    fn test_precedence() {
+        let dir = std::env::temp_dir().join(format!("alumy-conf-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.conf");
+        fs::write(&path, "level = warn\nfile = /tmp/from-file.log\nformat = binary\nmax_files = 9\n").unwrap();
+
+        let _env = crate::log::ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("ALUMY_CONF_TEST_LOG", "debug");
+        std::env::set_var("ALUMY_CONF_TEST_LOG_FORMAT", "logfmt");
+
+        let base = LogConfig::new("alumy-conf-test", "info").with_config_file(path.to_str().unwrap());
+
+        // Config file over the default level.
+        let resolved = resolve(&base).unwrap();
+        assert_eq!((resolved.level.as_deref(), resolved.filter.as_deref()), (Some("warn"), None));
+        assert_eq!(resolved.format, Some(LogFormat::Binary));
+        assert_eq!((resolved.max_size.as_deref(), resolved.max_files), (Some("1M"), Some(9)));
+
+        // Environment over the config file.
+        let resolved = resolve(&base.clone().with_env(true)).unwrap();
+        assert_eq!(resolved.filter.as_deref(), Some("debug"));
+        assert_eq!(resolved.format, Some(LogFormat::Logfmt));
+        assert_eq!(resolved.file.as_deref(), Some("/tmp/from-file.log"));
+
+        // Builder over the environment.
+        let resolved = resolve(&base.with_env(true).with_filter("error").with_format(LogFormat::Text)).unwrap();
+        assert_eq!(resolved.filter.as_deref(), Some("error"));
+        assert_eq!(resolved.format, Some(LogFormat::Text));
+
+        std::env::set_var("ALUMY_CONF_TEST_LOG_FORMAT", "xml");
+        assert!(resolve(&LogConfig::new("alumy-conf-test", "info").with_env(true)).is_err());
+
+        std::env::remove_var("ALUMY_CONF_TEST_LOG");
+        std::env::remove_var("ALUMY_CONF_TEST_LOG_FORMAT");
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
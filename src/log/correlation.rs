@@ -0,0 +1,296 @@
+//! Request IDs and W3C trace context carried by spans.
+//!
+//! Enter a [`request_span`] for the duration of a request and every record
+//! logged inside it carries the request ID: the text and logfmt formats print
+//! it with the span fields, the binary format stores it with each event, and
+//! OTLP log records get it as an attribute next to their trace and span IDs.
+//!
+//! ```
+//! use alumy::log::{request_span, uuid_v7, TraceParent};
+//!
+//! // The `traceparent` header of the incoming request, if any.
+//! let incoming = Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01");
+//!
+//! let span = request_span(uuid_v7(), incoming);
+//! let _enter = span.enter();
+//! alumy::info!("handling request");
+//!
+//! // Propagate the trace to the next service.
+//! let outgoing = TraceParent::current().map(|tp| tp.to_string());
+//! ```
+
+use std::collections::hash_map::RandomState;
+use std::fmt;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Span, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{Layer, Registry};
+
+/// Creates a `request` span carrying `request_id`, continuing the trace of the
+/// incoming `traceparent` header if it is valid.
+///
+/// The span is at `ERROR` level so that it is enabled whenever logging is.
+pub fn request_span(request_id: impl fmt::Display, traceparent: Option<&str>) -> Span {
+    match traceparent.and_then(TraceParent::parse) {
+        Some(traceparent) => tracing::error_span!("request", request_id = %request_id, %traceparent),
+        None => tracing::error_span!("request", request_id = %request_id),
+    }
+}
+
+/// Returns a new ULID, e.g. `01JAG3Q2W3Z8X9Y7VQ4M5N6P7R`: sortable by creation time, 26 characters.
+pub fn ulid() -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+    let value = (u128::from(unix_millis()) << 80) | (random_u128() >> 48);
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[(value >> (i * 5)) as usize & 0x1f] as char)
+        .collect()
+}
+
+/// Returns a new UUIDv7, e.g. `0192a3b4-c5d6-7e8f-9a0b-1c2d3e4f5a6b`: sortable by creation time.
+pub fn uuid_v7() -> String {
+    let random = random_u128();
+    let value = (u128::from(unix_millis()) << 80)
+        | (0x7 << 76)
+        | ((random >> 116) << 64)
+        | (0b10 << 62)
+        | (random & ((1 << 62) - 1));
+
+    let hex = format!("{value:032x}");
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+/// A W3C trace context `traceparent` header: `00-<trace-id>-<parent-id>-<flags>`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceParent {
+    pub trace_id: u128,
+    /// The ID of the span that made the request.
+    pub parent_id: u64,
+    pub sampled: bool,
+}
+
+impl TraceParent {
+    /// Parses a `traceparent` header, returning `None` if it is invalid.
+    ///
+    /// Headers of later versions are accepted as long as they start like version `00`.
+    pub fn parse(header: &str) -> Option<Self> {
+        let header = header.trim();
+        let mut parts = header.split('-');
+        let version = parts.next().filter(|v| is_hex(v, 2) && *v != "ff")?;
+        let trace_id = parts.next().filter(|v| is_hex(v, 32))?;
+        let parent_id = parts.next().filter(|v| is_hex(v, 16))?;
+        let flags = parts.next().filter(|v| is_hex(v, 2))?;
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+
+        let trace_id = u128::from_str_radix(trace_id, 16).ok().filter(|&id| id != 0)?;
+        let parent_id = u64::from_str_radix(parent_id, 16).ok().filter(|&id| id != 0)?;
+        let flags = u8::from_str_radix(flags, 16).ok()?;
+        Some(Self { trace_id, parent_id, sampled: flags & 1 == 1 })
+    }
+
+    /// Returns the header to send from the current span, which becomes the parent
+    /// of the remote one, or `None` outside a span or without the alumy logger.
+    pub fn current() -> Option<Self> {
+        Span::current()
+            .with_subscriber(|(id, dispatch)| {
+                let span = dispatch.downcast_ref::<Registry>()?.span(id)?;
+                let extensions = span.extensions();
+                let context = extensions.get::<SpanContext>()?;
+                Some(Self {
+                    trace_id: context.trace_id,
+                    parent_id: context.span_id,
+                    sampled: context.sampled,
+                })
+            })
+            .flatten()
+    }
+}
+
+impl fmt::Display for TraceParent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "00-{:032x}-{:016x}-{:02x}", self.trace_id, self.parent_id, self.sampled as u8)
+    }
+}
+
+fn is_hex(s: &str, len: usize) -> bool {
+    s.len() == len && s.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Trace context and request ID of a span, kept in its extensions.
+#[derive(Debug, Clone)]
+pub(crate) struct SpanContext {
+    pub(crate) trace_id: u128,
+    pub(crate) span_id: u64,
+    #[cfg_attr(not(feature = "otlp"), allow(dead_code))]
+    pub(crate) parent_id: Option<u64>,
+    pub(crate) sampled: bool,
+    /// The request ID of this span or the closest enclosing one.
+    pub(crate) request_id: Option<String>,
+}
+
+/// Returns the context of the span `event` was logged in, if any.
+pub(crate) fn event_context<S>(event: &Event<'_>, ctx: &Context<'_, S>) -> Option<SpanContext>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    ctx.event_span(event)?.extensions().get::<SpanContext>().cloned()
+}
+
+/// Assigns every span a [`SpanContext`], inherited from its parent or an incoming `traceparent`.
+pub(crate) struct CorrelationLayer;
+
+impl<S> Layer<S> for CorrelationLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let parent = span.parent().and_then(|p| p.extensions().get::<SpanContext>().cloned());
+
+        let mut fields = CorrelationFields::default();
+        attrs.record(&mut fields);
+        let remote = fields.traceparent.as_deref().and_then(TraceParent::parse);
+
+        let context = SpanContext {
+            trace_id: remote
+                .map(|r| r.trace_id)
+                .or(parent.as_ref().map(|p| p.trace_id))
+                .unwrap_or_else(|| random_u128().max(1)),
+            span_id: random_u64().max(1),
+            parent_id: remote.map(|r| r.parent_id).or(parent.as_ref().map(|p| p.span_id)),
+            sampled: remote.map(|r| r.sampled).or(parent.as_ref().map(|p| p.sampled)).unwrap_or(true),
+            request_id: fields.request_id.or_else(|| parent.and_then(|p| p.request_id)),
+        };
+        span.extensions_mut().insert(context);
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = CorrelationFields::default();
+        values.record(&mut fields);
+
+        if let Some(request_id) = fields.request_id {
+            let mut extensions = span.extensions_mut();
+            if let Some(context) = extensions.get_mut::<SpanContext>() {
+                context.request_id = Some(request_id);
+            }
+        }
+    }
+}
+
+#[derive(Default)]
+struct CorrelationFields {
+    request_id: Option<String>,
+    traceparent: Option<String>,
+}
+
+impl Visit for CorrelationFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "request_id" => self.request_id = Some(value.to_string()),
+            "traceparent" => self.traceparent = Some(value.to_string()),
+            _ => {}
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if matches!(field.name(), "request_id" | "traceparent") {
+            self.record_str(field, &format!("{value:?}"));
+        }
+    }
+}
+
+fn unix_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64 & ((1 << 48) - 1)
+}
+
+pub(crate) fn random_u64() -> u64 {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u64(COUNTER.fetch_add(1, Ordering::Relaxed));
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish()
+}
+
+fn random_u128() -> u128 {
+    u128::from(random_u64()) << 64 | u128::from(random_u64())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_ids() {
+        let id = ulid();
+        assert_eq!(id.len(), 26);
+        assert!(id.bytes().all(|b| b.is_ascii_digit() || b.is_ascii_uppercase()));
+        assert!(!id.contains(['I', 'L', 'O', 'U']));
+
+        let id = uuid_v7();
+        assert_eq!(id.len(), 36);
+        assert_eq!(id.as_bytes()[14], b'7', "version nibble in {id}");
+        assert!(matches!(id.as_bytes()[19], b'8' | b'9' | b'a' | b'b'), "variant bits in {id}");
+
+        // Both start with the creation time, so later IDs sort after earlier ones.
+        let earlier = uuid_v7();
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(uuid_v7() > earlier);
+        assert_ne!(ulid(), ulid());
+    }
+
+    #[test]
+    fn test_traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let tp = TraceParent::parse(header).unwrap();
+        assert_eq!(tp.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+        assert_eq!(tp.parent_id, 0x00f067aa0ba902b7);
+        assert!(tp.sampled);
+        assert_eq!(tp.to_string(), header);
+
+        assert!(TraceParent::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra").is_some());
+        assert!(TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra").is_none());
+        assert!(TraceParent::parse("ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-00000000000000000000000000000000-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01").is_none());
+        assert!(TraceParent::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01").is_none());
+        assert!(TraceParent::parse("not a header").is_none());
+    }
+
+    #[test]
+    fn test_span_context() {
+        let subscriber = tracing_subscriber::registry().with(CorrelationLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            assert_eq!(TraceParent::current(), None);
+
+            let request = request_span("req-1", Some("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00"));
+            let _request = request.enter();
+            let outer = TraceParent::current().unwrap();
+            assert_eq!(outer.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+            assert!(!outer.sampled);
+
+            let inner = tracing::error_span!("db");
+            let _inner = inner.enter();
+            let current = TraceParent::current().unwrap();
+            assert_eq!(current.trace_id, outer.trace_id);
+            assert_ne!(current.parent_id, outer.parent_id);
+
+            let request_id = inner.with_subscriber(|(id, dispatch)| {
+                let span = dispatch.downcast_ref::<Registry>().unwrap().span(id).unwrap();
+                let extensions = span.extensions();
+                extensions.get::<SpanContext>().unwrap().request_id.clone()
+            });
+            assert_eq!(request_id.flatten().as_deref(), Some("req-1"));
+        });
+    }
+}
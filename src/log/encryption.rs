@@ -0,0 +1,445 @@
+//! Encryption of rolling log files at rest.
+//!
+//! Every time a file is opened, on startup and after each rotation, a new
+//! stream starts with a header:
+//!
+//! ```text
+//! \xa1ALE1 | mode (0 symmetric, 1 X25519) | 16-byte salt | [32-byte ephemeral public key]
+//! ```
+//!
+//! The stream key is derived with HKDF-SHA256 from the configured key, or from
+//! an X25519 exchange with a fresh ephemeral key, and the salt. Each write is
+//! then sealed with ChaCha20-Poly1305 as one chunk, `u32 LE length | ciphertext`,
+//! with the length as associated data, numbered by the nonce so chunks cannot be
+//! reordered, or removed from the middle of a stream, unnoticed. A stream closed
+//! cleanly, at rotation or shutdown, ends with an empty chunk; a reader reports
+//! streams without it, whose end may have been lost. Encryption is the last step
+//! before the file, after the formatter, so it applies to the text, logfmt and
+//! binary formats alike.
+
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use sha2::Sha256;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Starts every stream; a file holds one stream per time it was opened.
+const MAGIC: &[u8] = b"\xa1ALE1";
+
+const MODE_SYMMETRIC: u8 = 0;
+const MODE_X25519: u8 = 1;
+
+const SALT_LEN: usize = 16;
+const TAG_LEN: usize = 16;
+/// Larger writes are split into chunks of this many bytes.
+const MAX_CHUNK: usize = 64 * 1024;
+
+/// The key log files are encrypted under (see [`LogConfig::with_encryption`](super::LogConfig::with_encryption)).
+#[derive(Clone, PartialEq, Eq)]
+pub enum EncryptionKey {
+    /// A 32-byte key, also needed to read the files.
+    Symmetric([u8; 32]),
+    /// An X25519 public key; the device cannot read its own logs, only the
+    /// holder of the secret key can (see [`generate_keypair`]).
+    Public([u8; 32]),
+}
+
+impl fmt::Debug for EncryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symmetric(_) => f.write_str("Symmetric(..)"),
+            Self::Public(key) => f.debug_tuple("Public").field(&hex(key)).finish(),
+        }
+    }
+}
+
+/// The key to read encrypted log files with, matching their [`EncryptionKey`].
+#[derive(Clone, PartialEq, Eq)]
+pub enum DecryptionKey {
+    Symmetric([u8; 32]),
+    /// The X25519 secret key whose public key the files were encrypted under.
+    Secret([u8; 32]),
+}
+
+impl fmt::Debug for DecryptionKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Symmetric(_) => f.write_str("Symmetric(..)"),
+            Self::Secret(_) => f.write_str("Secret(..)"),
+        }
+    }
+}
+
+/// Generates an X25519 key pair, returning the secret and the public key.
+///
+/// Configure the device with [`EncryptionKey::Public`] and keep the secret key
+/// off the device, for [`DecryptionKey::Secret`].
+pub fn generate_keypair() -> io::Result<([u8; 32], [u8; 32])> {
+    let secret = StaticSecret::from(random::<32>()?);
+    Ok((secret.to_bytes(), PublicKey::from(&secret).to_bytes()))
+}
+
+/// The contents of an encrypted log file, see [`decrypt_file`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Decrypted {
+    /// What would have been written without encryption.
+    pub data: Vec<u8>,
+    /// Streams that end without their final chunk: the file was not closed
+    /// cleanly, as after a crash or power loss, or its end was cut off. The
+    /// active file of a running application always ends with one.
+    pub unfinished: usize,
+    /// Bytes of torn chunks and headers that were skipped.
+    pub skipped: usize,
+}
+
+/// Reads and decrypts a log file written with encryption enabled.
+///
+/// The data is what would have been written without encryption: text
+/// records, or binary ones for [`decode_binary_bytes`](super::decode_binary_bytes).
+/// A chunk cut short by a power loss is skipped and counted in
+/// [`Decrypted::skipped`]. A wrong key or a modified file is an
+/// [`InvalidData`](io::ErrorKind::InvalidData) error.
+///
+/// # Examples
+///
+/// ```no_run
+/// use alumy::log::{decrypt_file, DecryptionKey};
+///
+/// let secret = [0u8; 32]; // loaded from a safe place
+/// let log = decrypt_file("logs/app.log", &DecryptionKey::Secret(secret))?;
+/// print!("{}", String::from_utf8_lossy(&log.data));
+/// # Ok::<(), std::io::Error>(())
+/// ```
+pub fn decrypt_file(path: impl AsRef<Path>, key: &DecryptionKey) -> io::Result<Decrypted> {
+    decrypt(&fs::read(path)?, key)
+}
+
+/// Decrypts the contents of an encrypted log file, as [`decrypt_file`] does.
+pub fn decrypt(mut data: &[u8], key: &DecryptionKey) -> io::Result<Decrypted> {
+    let mut decrypted = Decrypted::default();
+    let mut stream: Option<Stream> = None;
+
+    while !data.is_empty() {
+        if data.starts_with(MAGIC) {
+            if stream.take().is_some_and(|done| !done.finished) {
+                decrypted.unfinished += 1;
+            }
+            let Some((next, header_len)) = Stream::open(data, key)? else {
+                decrypted.skipped += data.len();
+                break;
+            };
+            stream = Some(next);
+            data = &data[header_len..];
+            continue;
+        }
+
+        let Some(current) = stream.as_mut() else {
+            return Err(invalid("missing header"));
+        };
+        if current.finished {
+            return Err(invalid("data after the end of a stream"));
+        }
+        let len = data.get(..4).map(|len| u32::from_le_bytes(len.try_into().unwrap()) as usize);
+        let sealed = len.and_then(|len| data.get(4..4 + len));
+        if let Some(opened) = sealed.and_then(|sealed| current.open_chunk(&data[..4], sealed)) {
+            data = &data[4 + opened.len() + TAG_LEN..];
+            current.finished = opened.is_empty();
+            decrypted.data.extend_from_slice(&opened);
+            continue;
+        }
+
+        // A chunk is torn if the file ends, or the stream started when the file
+        // was next opened begins, before the chunk's claimed end.
+        let end = len.map_or(data.len(), |len| 4 + len);
+        let resync = match find(&data[1..], MAGIC).map(|pos| 1 + pos) {
+            Some(pos) if pos < end => pos,
+            None if sealed.is_none() => data.len(),
+            _ => return Err(invalid("authentication failed, wrong key or modified file")),
+        };
+        decrypted.skipped += resync;
+        data = &data[resync..];
+    }
+    if stream.is_some_and(|last| !last.finished) {
+        decrypted.unfinished += 1;
+    }
+    Ok(decrypted)
+}
+
+/// The encrypting side of a stream, started for every newly opened file.
+pub(crate) struct Encryptor {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+}
+
+impl Encryptor {
+    /// Starts a new stream, returning it and the header to write first.
+    pub(crate) fn start(key: &EncryptionKey) -> io::Result<(Self, Vec<u8>)> {
+        let salt = random::<SALT_LEN>()?;
+        let mut header = MAGIC.to_vec();
+
+        let stream_key = match key {
+            EncryptionKey::Symmetric(key) => {
+                header.push(MODE_SYMMETRIC);
+                header.extend_from_slice(&salt);
+                derive(key, &salt, &[MODE_SYMMETRIC])
+            }
+            EncryptionKey::Public(recipient) => {
+                let ephemeral = StaticSecret::from(random::<32>()?);
+                let ephemeral_public = PublicKey::from(&ephemeral);
+                let shared = ephemeral.diffie_hellman(&PublicKey::from(*recipient));
+                if !shared.was_contributory() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "invalid X25519 public key"));
+                }
+                header.push(MODE_X25519);
+                header.extend_from_slice(&salt);
+                header.extend_from_slice(ephemeral_public.as_bytes());
+                derive(shared.as_bytes(), &salt, &x25519_info(ephemeral_public.as_bytes(), recipient))
+            }
+        };
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&stream_key));
+        Ok((Self { cipher, counter: 0 }, header))
+    }
+
+    /// Seals `plaintext` as one or more chunks appended to `out`.
+    pub(crate) fn seal(&mut self, plaintext: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        for part in plaintext.chunks(MAX_CHUNK) {
+            self.seal_chunk(part, out)?;
+        }
+        Ok(())
+    }
+
+    /// Appends the empty chunk that ends the stream to `out`.
+    pub(crate) fn finish(mut self, out: &mut Vec<u8>) -> io::Result<()> {
+        self.seal_chunk(&[], out)
+    }
+
+    fn seal_chunk(&mut self, part: &[u8], out: &mut Vec<u8>) -> io::Result<()> {
+        let len = ((part.len() + TAG_LEN) as u32).to_le_bytes();
+        let sealed = self
+            .cipher
+            .encrypt(&nonce(self.counter), Payload { msg: part, aad: &len })
+            .map_err(|_| io::Error::other("log encryption failed"))?;
+        self.counter += 1;
+        out.extend_from_slice(&len);
+        out.extend_from_slice(&sealed);
+        Ok(())
+    }
+}
+
+/// The decrypting side of a stream.
+struct Stream {
+    cipher: ChaCha20Poly1305,
+    counter: u64,
+    /// Whether the final chunk was read.
+    finished: bool,
+}
+
+impl Stream {
+    /// Reads the header at the start of `data`, returning the stream and the
+    /// header length, or `None` if the header was cut short.
+    fn open(data: &[u8], key: &DecryptionKey) -> io::Result<Option<(Self, usize)>> {
+        let Some(&mode) = data.get(MAGIC.len()) else {
+            return Ok(None);
+        };
+        let start = MAGIC.len() + 1;
+        let Some(salt) = data.get(start..start + SALT_LEN) else {
+            return Ok(None);
+        };
+
+        let (stream_key, len) = match (mode, key) {
+            (MODE_SYMMETRIC, DecryptionKey::Symmetric(key)) => {
+                (derive(key, salt, &[MODE_SYMMETRIC]), start + SALT_LEN)
+            }
+            (MODE_X25519, DecryptionKey::Secret(secret)) => {
+                let Some(ephemeral) = data.get(start + SALT_LEN..start + SALT_LEN + 32) else {
+                    return Ok(None);
+                };
+                let ephemeral: [u8; 32] = ephemeral.try_into().unwrap();
+                let secret = StaticSecret::from(*secret);
+                let recipient = PublicKey::from(&secret);
+                let shared = secret.diffie_hellman(&PublicKey::from(ephemeral));
+                let info = x25519_info(&ephemeral, recipient.as_bytes());
+                (derive(shared.as_bytes(), salt, &info), start + SALT_LEN + 32)
+            }
+            (MODE_SYMMETRIC, _) => return Err(invalid("file is encrypted with a symmetric key")),
+            (MODE_X25519, _) => return Err(invalid("file is encrypted with a public key")),
+            _ => return Err(invalid("unknown encryption mode")),
+        };
+
+        let cipher = ChaCha20Poly1305::new(Key::from_slice(&stream_key));
+        let stream = Self {
+            cipher,
+            counter: 0,
+            finished: false,
+        };
+        Ok(Some((stream, len)))
+    }
+
+    /// Decrypts the next chunk with its length prefix `len`, or returns `None`
+    /// if it does not authenticate.
+    fn open_chunk(&mut self, len: &[u8], sealed: &[u8]) -> Option<Vec<u8>> {
+        let payload = Payload { msg: sealed, aad: len };
+        let plain = self.cipher.decrypt(&nonce(self.counter), payload).ok()?;
+        self.counter += 1;
+        Some(plain)
+    }
+}
+
+fn derive(ikm: &[u8], salt: &[u8], info: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(salt), ikm)
+        .expand(&[b"alumy log v1 ".as_slice(), info].concat(), &mut key)
+        .expect("32 bytes is a valid HKDF-SHA256 output length");
+    key
+}
+
+fn x25519_info(ephemeral: &[u8; 32], recipient: &[u8; 32]) -> Vec<u8> {
+    [&[MODE_X25519][..], ephemeral, recipient].concat()
+}
+
+/// The nonce of the chunk numbered `counter` in its stream.
+fn nonce(counter: u64) -> Nonce {
+    let mut nonce = [0u8; 12];
+    nonce[4..].copy_from_slice(&counter.to_be_bytes());
+    Nonce::from(nonce)
+}
+
+fn random<const N: usize>() -> io::Result<[u8; N]> {
+    let mut bytes = [0u8; N];
+    getrandom::getrandom(&mut bytes).map_err(io::Error::from)?;
+    Ok(bytes)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{b:02x}")).collect()
+}
+
+fn invalid(what: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid encrypted log: {what}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encrypt(key: &EncryptionKey, writes: &[&[u8]]) -> Vec<u8> {
+        let (mut encryptor, mut out) = Encryptor::start(key).unwrap();
+        for write in writes {
+            encryptor.seal(write, &mut out).unwrap();
+        }
+        out
+    }
+
+    fn encrypt_finished(key: &EncryptionKey, writes: &[&[u8]]) -> Vec<u8> {
+        let (mut encryptor, mut out) = Encryptor::start(key).unwrap();
+        for write in writes {
+            encryptor.seal(write, &mut out).unwrap();
+        }
+        encryptor.finish(&mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_symmetric_roundtrip() {
+        let key = [7u8; 32];
+        let mut file = encrypt_finished(&EncryptionKey::Symmetric(key), &[b"first line\n", b"second line\n"]);
+        assert!(find(&file, b"first").is_none(), "plaintext leaked");
+
+        // The file was opened again after a restart: a second stream follows.
+        file.extend(encrypt(&EncryptionKey::Symmetric(key), &[b"after restart\n"]));
+        let plain = decrypt(&file, &DecryptionKey::Symmetric(key)).unwrap();
+        assert_eq!(plain.data, b"first line\nsecond line\nafter restart\n");
+        assert_eq!((plain.unfinished, plain.skipped), (1, 0));
+
+        let err = decrypt(&file, &DecryptionKey::Symmetric([8u8; 32])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(decrypt(&file, &DecryptionKey::Secret(key)).is_err());
+    }
+
+    #[test]
+    fn test_public_key_roundtrip() {
+        let (secret, public) = generate_keypair().unwrap();
+        let large = vec![b'x'; MAX_CHUNK + 10];
+        let file = encrypt(&EncryptionKey::Public(public), &[b"hello\n", &large]);
+
+        let plain = decrypt(&file, &DecryptionKey::Secret(secret)).unwrap();
+        assert_eq!(&plain.data[..6], b"hello\n");
+        assert_eq!(plain.data.len(), 6 + large.len());
+
+        let (other, _) = generate_keypair().unwrap();
+        assert!(decrypt(&file, &DecryptionKey::Secret(other)).is_err());
+    }
+
+    #[test]
+    fn test_tampering_and_truncation() {
+        let key = EncryptionKey::Symmetric([1u8; 32]);
+        let reader = DecryptionKey::Symmetric([1u8; 32]);
+        let file = encrypt(&key, &[b"one\n", b"two\n"]);
+
+        // A torn last chunk is ignored, also when the file was reopened after it.
+        let torn = &file[..file.len() - 3];
+        let plain = decrypt(torn, &reader).unwrap();
+        assert_eq!((plain.data.as_slice(), plain.skipped), (&b"one\n"[..], 4 + 4 + TAG_LEN - 3));
+        let mut reopened = torn.to_vec();
+        reopened.extend(encrypt(&key, &[b"three\n"]));
+        let plain = decrypt(&reopened, &reader).unwrap();
+        assert_eq!(plain.data, b"one\nthree\n");
+        assert_eq!((plain.unfinished, plain.skipped), (2, 4 + 4 + TAG_LEN - 3));
+
+        let mut modified = file.clone();
+        let last = modified.len() - 20;
+        modified[last] ^= 1;
+        assert!(decrypt(&modified, &reader).is_err());
+
+        // A complete chunk modified in the first stream is not mistaken for a torn one.
+        let mut two_streams = file.clone();
+        two_streams.extend(encrypt(&key, &[b"three\n"]));
+        two_streams[last] ^= 1;
+        let err = decrypt(&two_streams, &reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+
+        // Swapping the two chunks breaks the nonce sequence.
+        let header_len = MAGIC.len() + 1 + SALT_LEN;
+        let chunk_len = 4 + 4 + TAG_LEN;
+        let mut swapped = file[..header_len].to_vec();
+        swapped.extend_from_slice(&file[header_len + chunk_len..]);
+        swapped.extend_from_slice(&file[header_len..header_len + chunk_len]);
+        assert!(decrypt(&swapped, &reader).is_err());
+    }
+
+    #[test]
+    fn test_final_chunk() {
+        let key = EncryptionKey::Symmetric([2u8; 32]);
+        let reader = DecryptionKey::Symmetric([2u8; 32]);
+        let file = encrypt_finished(&key, &[b"one\n", b"two\n"]);
+        let plain = decrypt(&file, &reader).unwrap();
+        assert_eq!(plain.data, b"one\ntwo\n");
+        assert_eq!((plain.unfinished, plain.skipped), (0, 0));
+
+        // Cutting off whole chunks at the end loses the final one.
+        let final_len = 4 + TAG_LEN;
+        let cut = decrypt(&file[..file.len() - final_len - (4 + 4 + TAG_LEN)], &reader).unwrap();
+        assert_eq!((cut.data.as_slice(), cut.unfinished), (&b"one\n"[..], 1));
+
+        // Nothing may follow the final chunk but a new stream.
+        let mut appended = file.clone();
+        appended.extend_from_slice(&file[file.len() - final_len..]);
+        assert!(decrypt(&appended, &reader).is_err());
+
+        // A modified length is an error, not a torn chunk.
+        let mut resized = encrypt(&key, &[b"one\n"]);
+        let header_len = MAGIC.len() + 1 + SALT_LEN;
+        resized[header_len] += 1;
+        resized.push(0);
+        assert!(decrypt(&resized, &reader).is_err());
+    }
+}
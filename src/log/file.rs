@@ -0,0 +1,537 @@
+use std::collections::VecDeque;
+use std::fs::{File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(unix)]
+use std::sync::OnceLock;
+#[cfg(unix)]
+use std::sync::atomic::AtomicBool;
+use std::time::{Duration, Instant};
+
+use super::stats::Stats;
+use super::writer::Sink;
+
+/// How often a failed sink is reopened and a healthy one is checked for deletion.
+const RETRY_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Bytes written since the last successful flush that a [`ResilientWriter`]
+/// keeps for the stderr fallback. Comfortably above the sinks' own buffers.
+const MAX_UNFLUSHED: usize = 64 * 1024;
+
+/// Bumped by [`reopen`] and by SIGHUP; [`ReopenFile`] reopens when it changes.
+static REOPEN_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// How log output is written to the configured file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FileMode {
+    /// Size-based rotation by alumy itself (default).
+    #[default]
+    Rolling,
+    /// A plain append-only file rotated by an external tool such as `logrotate`.
+    /// The size and file count given to `with_file` are ignored.
+    ///
+    /// The file is reopened after SIGHUP or [`reopen`], and whenever it is
+    /// replaced (inode change) or truncated underneath the logger. A SIGHUP
+    /// handler installed before the logger is still called.
+    Reopen,
+    /// Size-based rotation that is safe when several processes log to the same file.
+    ///
+    /// Each flush is appended with a single `O_APPEND` write and rotation is
+    /// coordinated through a `flock` on `<file>.lock`. Unix only.
+    Shared,
+    /// A preallocated file of the size given to `with_file`, at least 4224
+    /// bytes, overwritten in place as a circular buffer. The file count is ignored.
+    ///
+    /// The file never grows and is never renamed, which suits small flash
+    /// partitions. It is not plain text; use [`read_circular`](super::read_circular)
+    /// to dump its records in order.
+    Circular,
+}
+
+/// Asks file sinks in [`FileMode::Reopen`] to reopen their file before the next write.
+pub fn reopen() {
+    REOPEN_GENERATION.fetch_add(1, Ordering::Relaxed);
+}
+
+/// Whether the SIGHUP handler has been installed.
+#[cfg(unix)]
+static SIGHUP_INSTALLED: AtomicBool = AtomicBool::new(false);
+
+/// The SIGHUP action in place before ours, called after reopening.
+#[cfg(unix)]
+static PREVIOUS_SIGHUP: OnceLock<libc::sigaction> = OnceLock::new();
+
+/// Installs a SIGHUP handler that triggers [`reopen`] and then calls the
+/// handler installed before it, if any.
+#[cfg(unix)]
+pub(crate) fn install_reopen_signal() -> io::Result<()> {
+    extern "C" fn on_sighup(signal: libc::c_int, info: *mut libc::siginfo_t, context: *mut libc::c_void) {
+        REOPEN_GENERATION.fetch_add(1, Ordering::Relaxed);
+
+        let Some(previous) = PREVIOUS_SIGHUP.get() else {
+            return;
+        };
+        if previous.sa_sigaction == libc::SIG_DFL || previous.sa_sigaction == libc::SIG_IGN {
+            return;
+        }
+        unsafe {
+            if previous.sa_flags & libc::SA_SIGINFO != 0 {
+                let handler: extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void) =
+                    std::mem::transmute(previous.sa_sigaction);
+                handler(signal, info, context);
+            } else {
+                let handler: extern "C" fn(libc::c_int) = std::mem::transmute(previous.sa_sigaction);
+                handler(signal);
+            }
+        }
+    }
+
+    if SIGHUP_INSTALLED.swap(true, Ordering::AcqRel) {
+        return Ok(());
+    }
+
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sighup as extern "C" fn(libc::c_int, *mut libc::siginfo_t, *mut libc::c_void)
+            as libc::sighandler_t;
+        action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+        libc::sigemptyset(&mut action.sa_mask);
+        let mut previous: libc::sigaction = std::mem::zeroed();
+        if libc::sigaction(libc::SIGHUP, &action, &mut previous) != 0 {
+            SIGHUP_INSTALLED.store(false, Ordering::Release);
+            return Err(io::Error::last_os_error());
+        }
+        let _ = PREVIOUS_SIGHUP.set(previous);
+    }
+    Ok(())
+}
+
+/// Append-only log file that follows external rotation.
+pub(crate) struct ReopenFile {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    identity: Option<(u64, u64)>,
+    size: u64,
+    generation: u64,
+    last_check: Instant,
+    stats: Arc<Stats>,
+}
+
+impl ReopenFile {
+    pub(crate) fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().append(true).create(true).open(&path)?;
+        let metadata = file.metadata()?;
+
+        Ok(Self {
+            identity: file_identity(&metadata),
+            size: metadata.len(),
+            writer: BufWriter::new(file),
+            path,
+            generation: REOPEN_GENERATION.load(Ordering::Relaxed),
+            last_check: Instant::now(),
+            stats: Arc::default(),
+        })
+    }
+
+    /// Counts reopens as rotations in `stats`.
+    pub(crate) fn with_stats(mut self, stats: Arc<Stats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Returns how large the file should be on disk, leaving out what is still buffered.
+    fn flushed_size(&self) -> u64 {
+        self.size - self.writer.buffer().len() as u64
+    }
+
+    /// Returns true if the file was rotated or truncated since it was opened.
+    fn replaced(&self) -> bool {
+        match std::fs::metadata(&self.path) {
+            Ok(metadata) => {
+                file_identity(&metadata) != self.identity || metadata.len() < self.flushed_size()
+            }
+            Err(_) => true,
+        }
+    }
+
+    fn reopen_if_needed(&mut self) -> io::Result<()> {
+        let generation = REOPEN_GENERATION.load(Ordering::Relaxed);
+        let requested = generation != self.generation;
+
+        if !requested {
+            if self.last_check.elapsed() < RETRY_INTERVAL {
+                return Ok(());
+            }
+            self.last_check = Instant::now();
+            if !self.replaced() {
+                return Ok(());
+            }
+        }
+
+        self.writer.flush()?;
+        *self = Self::open(&self.path)?.with_stats(self.stats.clone());
+        self.generation = generation;
+        Stats::inc(&self.stats.rotations);
+        Ok(())
+    }
+}
+
+impl Write for ReopenFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.reopen_if_needed()?;
+        self.writer.write_all(buf)?;
+        self.size += buf.len() as u64;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Sink for ReopenFile {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        self.writer.get_ref().sync_data()
+    }
+}
+
+#[cfg(unix)]
+fn file_identity(metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &std::fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// How the records passed to a [`ResilientWriter`] are encoded, for sinks that
+/// do not take plain text.
+#[derive(Clone, Copy)]
+pub(crate) struct RecordCodec {
+    /// Renders records as text for the stderr fallback.
+    pub(crate) to_text: fn(&[u8]) -> Vec<u8>,
+    /// Encodes a note from the logger itself, given without a trailing newline.
+    pub(crate) notice: fn(&str) -> Vec<u8>,
+}
+
+impl Default for RecordCodec {
+    fn default() -> Self {
+        Self {
+            to_text: |buf| buf.to_vec(),
+            notice: |text| format!("{text}\n").into_bytes(),
+        }
+    }
+}
+
+/// Wraps a file sink so that I/O errors never silently discard log lines.
+///
+/// When a write fails (disk full, file system gone, ...) the sink is dropped and
+/// output falls back to stderr. Every [`RETRY_INTERVAL`] the sink is recreated
+/// with `open`; once that succeeds a single record stating how many lines were
+/// diverted is written to the new file. If `path` is set, a healthy sink is
+/// also recreated when its file has been deleted.
+///
+/// Records written since the last successful flush are kept as well, since a
+/// buffering sink usually reports a full disk only when flushed; they are
+/// diverted to stderr along with the rest when the sink fails.
+pub(crate) struct ResilientWriter<W, F> {
+    open: F,
+    codec: RecordCodec,
+    writer: Option<W>,
+    path: Option<PathBuf>,
+    unflushed: VecDeque<Vec<u8>>,
+    unflushed_len: usize,
+    lost: u64,
+    last_check: Instant,
+    retry_interval: Duration,
+    stats: Arc<Stats>,
+}
+
+impl<W: Write, F: FnMut() -> io::Result<W>> ResilientWriter<W, F> {
+    pub(crate) fn new(writer: W, open: F) -> Self {
+        Self {
+            open,
+            codec: RecordCodec::default(),
+            writer: Some(writer),
+            path: None,
+            unflushed: VecDeque::new(),
+            unflushed_len: 0,
+            lost: 0,
+            last_check: Instant::now(),
+            retry_interval: RETRY_INTERVAL,
+            stats: Arc::default(),
+        }
+    }
+
+    /// Counts write failures in `stats`.
+    pub(crate) fn with_stats(mut self, stats: Arc<Stats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    /// Sets how records are rendered on stderr and how the recovery record is encoded.
+    pub(crate) fn with_codec(mut self, codec: RecordCodec) -> Self {
+        self.codec = codec;
+        self
+    }
+
+    /// Recreates the sink when `path` disappears from the file system.
+    pub(crate) fn watch_path(mut self, path: impl Into<PathBuf>) -> Self {
+        self.path = Some(path.into());
+        self
+    }
+
+    #[cfg(test)]
+    fn with_retry_interval(mut self, interval: Duration) -> Self {
+        self.retry_interval = interval;
+        self
+    }
+
+    fn check(&mut self) {
+        if self.last_check.elapsed() < self.retry_interval {
+            return;
+        }
+        self.last_check = Instant::now();
+
+        let deleted = self.path.as_ref().is_some_and(|p| !p.exists());
+        if self.writer.is_some() && !deleted {
+            return;
+        }
+
+        match (self.open)() {
+            Ok(mut writer) => {
+                if self.lost > 0 {
+                    let record = (self.codec.notice)(&format!(
+                        "alumy: log file recovered after a write failure, {} line(s) were written to stderr instead",
+                        self.lost
+                    ));
+                    if writer.write_all(&record).is_err() {
+                        return;
+                    }
+                    self.lost = 0;
+                }
+                self.writer = Some(writer);
+            }
+            Err(e) if self.writer.is_some() => {
+                eprintln!("alumy: failed to recreate deleted log file: {e}");
+            }
+            Err(_) => {}
+        }
+    }
+
+    fn fail(&mut self, e: io::Error) {
+        Stats::inc(&self.stats.write_errors);
+        if self.writer.take().is_some() {
+            eprintln!("alumy: log file write failed, falling back to stderr: {e}");
+        }
+        self.last_check = Instant::now();
+
+        // The sink may not have written these out, so they could be lost with it.
+        while let Some(buf) = self.unflushed.pop_front() {
+            self.divert(&buf);
+        }
+        self.unflushed_len = 0;
+    }
+
+    /// Writes `buf` to stderr and counts its lines as lost.
+    fn divert(&mut self, buf: &[u8]) {
+        let text = (self.codec.to_text)(buf);
+        self.lost += text.iter().filter(|&&b| b == b'\n').count().max(1) as u64;
+        let _ = io::stderr().write_all(&text);
+    }
+
+    fn keep_unflushed(&mut self, buf: &[u8]) {
+        self.unflushed.push_back(buf.to_vec());
+        self.unflushed_len += buf.len();
+        while self.unflushed_len > MAX_UNFLUSHED {
+            match self.unflushed.pop_front() {
+                Some(old) => self.unflushed_len -= old.len(),
+                None => break,
+            }
+        }
+    }
+}
+
+impl<W: Write, F: FnMut() -> io::Result<W>> Write for ResilientWriter<W, F> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.check();
+
+        if let Some(writer) = self.writer.as_mut() {
+            match writer.write_all(buf) {
+                Ok(()) => {
+                    self.keep_unflushed(buf);
+                    return Ok(buf.len());
+                }
+                Err(e) => self.fail(e),
+            }
+        }
+
+        self.divert(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            match writer.flush() {
+                Ok(()) => {
+                    self.unflushed.clear();
+                    self.unflushed_len = 0;
+                }
+                Err(e) => self.fail(e),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<W: Sink, F: FnMut() -> io::Result<W>> Sink for ResilientWriter<W, F> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        if let Some(writer) = self.writer.as_mut() {
+            if let Err(e) = writer.sync_data() {
+                self.fail(e);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A sink that fails once `healthy` is cleared.
+    #[derive(Clone)]
+    struct Flaky {
+        data: Arc<Mutex<Vec<u8>>>,
+        healthy: Arc<Mutex<bool>>,
+    }
+
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if !*self.healthy.lock().unwrap() {
+                return Err(io::Error::other("no space left on device"));
+            }
+            self.data.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_reopen_file_follows_rotation() {
+        let dir = std::env::temp_dir().join(format!("alumy-reopen-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+        let rotated = dir.join("app.log.1");
+
+        let mut file = ReopenFile::open(&path).unwrap();
+        file.write_all(b"first\n").unwrap();
+        assert!(!file.replaced(), "Buffered bytes should not look like a truncation");
+        file.flush().unwrap();
+        assert!(!file.replaced());
+
+        std::fs::rename(&path, &rotated).unwrap();
+        assert!(file.replaced());
+        reopen();
+        file.write_all(b"second\n").unwrap();
+        file.flush().unwrap();
+
+        assert_eq!(std::fs::read_to_string(&rotated).unwrap(), "first\n");
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "second\n");
+        assert_eq!(file.stats.snapshot(0).rotations, 1);
+
+        std::fs::OpenOptions::new().write(true).truncate(true).open(&path).unwrap();
+        assert!(file.replaced());
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_recovers_and_reports_lost_lines() {
+        let flaky = Flaky {
+            data: Arc::new(Mutex::new(Vec::new())),
+            healthy: Arc::new(Mutex::new(true)),
+        };
+        let healthy = flaky.healthy.clone();
+        let reopen = flaky.clone();
+        let mut writer = ResilientWriter::new(flaky.clone(), move || {
+            if *reopen.healthy.lock().unwrap() {
+                Ok(reopen.clone())
+            } else {
+                Err(io::Error::other("still full"))
+            }
+        })
+        .with_retry_interval(Duration::from_millis(10));
+
+        writer.write_all(b"one\n").unwrap();
+        writer.flush().unwrap();
+        *healthy.lock().unwrap() = false;
+        writer.write_all(b"two\n").unwrap();
+        writer.write_all(b"three\nfour\n").unwrap();
+        assert!(writer.writer.is_none());
+        assert_eq!(writer.lost, 3);
+        assert_eq!(writer.stats.snapshot(0).write_errors, 1);
+
+        *healthy.lock().unwrap() = true;
+        std::thread::sleep(Duration::from_millis(20));
+        writer.write_all(b"five\n").unwrap();
+
+        let data = String::from_utf8(flaky.data.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = data.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert_eq!(lines[0], "one");
+        assert!(lines[1].contains("3 line(s)"));
+        assert_eq!(lines[2], "five");
+        assert_eq!(writer.lost, 0);
+    }
+
+    #[test]
+    fn test_failed_flush_diverts_buffered_lines() {
+        let flaky = Flaky {
+            data: Arc::new(Mutex::new(Vec::new())),
+            healthy: Arc::new(Mutex::new(true)),
+        };
+        let healthy = flaky.healthy.clone();
+        let reopen = flaky.clone();
+        let mut writer = ResilientWriter::new(BufWriter::new(flaky.clone()), move || {
+            if *reopen.healthy.lock().unwrap() {
+                Ok(BufWriter::new(reopen.clone()))
+            } else {
+                Err(io::Error::other("still full"))
+            }
+        })
+        .with_retry_interval(Duration::from_millis(10));
+
+        writer.write_all(b"one\n").unwrap();
+        writer.flush().unwrap();
+        writer.write_all(b"two\n").unwrap();
+        writer.write_all(b"three\n").unwrap();
+        *healthy.lock().unwrap() = false;
+        writer.flush().unwrap();
+        assert!(writer.writer.is_none());
+        assert_eq!(writer.lost, 2);
+        assert!(writer.unflushed.is_empty());
+
+        *healthy.lock().unwrap() = true;
+        std::thread::sleep(Duration::from_millis(20));
+        writer.write_all(b"four\n").unwrap();
+        writer.flush().unwrap();
+
+        let data = String::from_utf8(flaky.data.lock().unwrap().clone()).unwrap();
+        let lines: Vec<_> = data.lines().collect();
+        assert_eq!(lines.len(), 3, "{data}");
+        assert_eq!(lines[0], "one");
+        assert!(lines[1].contains("2 line(s)"));
+        assert_eq!(lines[2], "four");
+    }
+}
@@ -0,0 +1,226 @@
+use std::fmt::Write as _;
+use std::sync::Arc;
+use tracing::{Event, Subscriber};
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::log_init::env_filter;
+use super::logfmt::write_value;
+use super::rolling::HeaderFn;
+use super::LogConfig;
+
+pub(crate) struct UptimeTime;
+
+impl FormatTime for UptimeTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> std::fmt::Result {
+        let duration = crate::sys::uptime::uptime_duration();
+        write!(w, "[{:>6}.{:03}]", duration.as_secs(), duration.subsec_millis())
+    }
+}
+
+/// Layout of each log record.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LogFormat {
+    /// Human readable text, as produced by `tracing-subscriber` (default).
+    #[default]
+    Text,
+    /// logfmt: `ts=... level=info target=... msg="..." key=value`, with span fields appended.
+    Logfmt,
+    /// Compact binary records for storage-starved devices; requires [`FileMode::Rolling`](super::FileMode::Rolling).
+    ///
+    /// Callsite metadata and repeated strings are stored once per file, and each
+    /// event holds a callsite ID, the uptime and typed field values. Span fields
+    /// are not recorded. Read the file back with [`decode_binary`](super::decode_binary).
+    Binary,
+}
+
+/// Where a log line came from: application, build, machine and process.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Identity {
+    pub(crate) app: String,
+    pub(crate) version: String,
+    pub(crate) host: Option<String>,
+    pub(crate) pid: u32,
+    pub(crate) boot_id: Option<String>,
+}
+
+impl Identity {
+    pub(crate) fn new(log_config: &LogConfig) -> Self {
+        Self {
+            app: log_config.name.clone().unwrap_or_else(|| crate::version::name().to_string()),
+            version: log_config.version.clone().unwrap_or_else(|| crate::version::version().to_string()),
+            host: crate::sys::host::hostname(),
+            pid: std::process::id(),
+            boot_id: crate::sys::host::boot_id(),
+        }
+    }
+
+    /// Renders the identity as `key=value` pairs, skipping unknown values.
+    ///
+    /// Values are quoted as in [`LogFormat::Logfmt`] where needed.
+    pub(crate) fn to_fields(&self) -> String {
+        let mut s = String::new();
+        let mut pair = |key: &str, value: &str| {
+            if !s.is_empty() {
+                s.push(' ');
+            }
+            let _ = write!(s, "{key}=");
+            write_value(&mut s, value);
+        };
+        pair("app", &self.app);
+        pair("version", &self.version);
+        if let Some(host) = &self.host {
+            pair("host", host);
+        }
+        pair("pid", &self.pid.to_string());
+        if let Some(boot_id) = &self.boot_id {
+            pair("boot", boot_id);
+        }
+        s
+    }
+}
+
+/// Extra text appended to the header of every log file, e.g. application build info.
+#[derive(Clone)]
+pub struct HeaderHook(pub(crate) Arc<dyn Fn() -> String + Send + Sync>);
+
+impl HeaderHook {
+    pub fn new(f: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        Self(Arc::new(f))
+    }
+}
+
+impl std::fmt::Debug for HeaderHook {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("HeaderHook(..)")
+    }
+}
+
+/// Formats the current local time like the `iso` log timestamps, falling back to UTC.
+pub(crate) fn local_timestamp() -> String {
+    let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+    now.format(time::macros::format_description!(
+        "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+    ))
+    .unwrap_or_default()
+}
+
+/// Builds the preamble written whenever a new log file is opened.
+///
+/// It records where the file came from (identity and build), when logging
+/// started, the uptime at the time the file was opened and the main settings
+/// in effect, followed by the output of the configured [`HeaderHook`].
+pub(crate) fn file_header(log_config: &LogConfig) -> HeaderFn {
+    let identity = Identity::new(log_config).to_fields();
+    let build = format!(
+        "alumy {} ({}-{}, {})",
+        crate::version::version(),
+        std::env::consts::ARCH,
+        std::env::consts::OS,
+        if cfg!(debug_assertions) { "debug" } else { "release" }
+    );
+    let started = local_timestamp();
+    let config = format!(
+        "filter={:?} file={:?} mode={:?} max_size={} max_files={} format={:?}",
+        env_filter(log_config).to_string(),
+        log_config.file.as_deref().unwrap_or_default(),
+        log_config.file_mode(),
+        log_config.max_size(),
+        log_config.max_files(),
+        log_config.format(),
+    );
+    let hook = log_config.header_hook.clone();
+
+    Arc::new(move || {
+        let uptime = crate::sys::uptime::uptime_duration();
+        let mut header = format!(
+            "# log opened {}\n# {identity}\n# build: {build}\n# started: {started}, uptime: {}.{:03}s\n# config: {config}\n",
+            local_timestamp(),
+            uptime.as_secs(),
+            uptime.subsec_millis(),
+        );
+        if let Some(hook) = &hook {
+            header.push_str(&(hook.0)());
+            if !header.ends_with('\n') {
+                header.push('\n');
+            }
+        }
+        header.into_bytes()
+    })
+}
+
+/// Prefixes every record produced by `inner` with a fixed stamp.
+pub(crate) struct Stamped<F> {
+    inner: F,
+    stamp: Option<String>,
+}
+
+impl<F> Stamped<F> {
+    pub(crate) fn new(inner: F, stamp: Option<String>) -> Self {
+        Self { inner, stamp }
+    }
+}
+
+impl<S, N, F> FormatEvent<S, N> for Stamped<F>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+    F: FormatEvent<S, N>,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> std::fmt::Result {
+        if let Some(stamp) = &self.stamp {
+            write!(writer, "{stamp} ")?;
+        }
+        self.inner.format_event(ctx, writer, event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identity_fields() {
+        let identity = Identity {
+            app: "my-app".to_string(),
+            version: "1.2.3".to_string(),
+            host: Some("dev01".to_string()),
+            pid: 42,
+            boot_id: None,
+        };
+        assert_eq!(identity.to_fields(), "app=my-app version=1.2.3 host=dev01 pid=42");
+
+        let identity = Identity { app: "my app".to_string(), version: "1.0 \"beta\"".to_string(), ..identity };
+        assert_eq!(identity.to_fields(), r#"app="my app" version="1.0 \"beta\"" host=dev01 pid=42"#);
+    }
+
+    #[test]
+    fn test_file_header() {
+        let config = LogConfig::new("test", "info")
+            .with_file("app.log", "10K", 3)
+            .with_version("1.2.3")
+            .with_header_hook(|| "# commit: abc123\n".to_string());
+        let header = String::from_utf8(file_header(&config)()).unwrap();
+        let lines: Vec<_> = header.lines().collect();
+
+        assert!(lines[0].starts_with("# log opened "));
+        assert!(lines[1].starts_with("# app=test version=1.2.3"));
+        assert!(lines[2].starts_with("# build: alumy "));
+        assert!(lines[3].starts_with("# started: "));
+        assert_eq!(lines[4], "# config: filter=\"info\" file=\"app.log\" mode=Rolling max_size=10240 max_files=3 format=Text");
+        assert_eq!(lines[5], "# commit: abc123");
+    }
+
+    #[test]
+    fn test_identity_defaults() {
+        let identity = Identity::new(&LogConfig::new("test", "info"));
+        assert_eq!(identity.app, "test");
+        assert_eq!(identity.version, crate::version::version());
+        assert_eq!(identity.pid, std::process::id());
+
+        let identity = Identity::new(&LogConfig::new("test", "info").with_version("9.9.9"));
+        assert_eq!(identity.version, "9.9.9");
+    }
+}
@@ -0,0 +1,171 @@
+use std::fmt;
+
+/// Bytes shown by default before a dump is truncated.
+const DEFAULT_MAX_LEN: usize = 256;
+const BYTES_PER_ROW: usize = 16;
+
+/// Formats a byte buffer as a hex dump when displayed; used by [`hexdump!`](crate::hexdump).
+///
+/// The default layout is the classic one, one row of offset, hex and ASCII per
+/// 16 bytes, starting on a new line so the rows line up under the record:
+///
+/// ```text
+/// 00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|
+/// 00000010  01                                                |.|
+/// ```
+///
+/// [`compact`](Self::compact) puts the hex bytes on a single line instead.
+/// Bytes beyond [`max_len`](Self::max_len) (256 by default) are left out and
+/// replaced by the truncation marker and the number of bytes left out.
+///
+/// ```
+/// use alumy::log::HexDump;
+///
+/// let dump = HexDump::new(b"\x02OK\x03").compact(true);
+/// assert_eq!(dump.to_string(), "02 4f 4b 03");
+///
+/// let dump = HexDump::new(&[0u8; 8]).compact(true).max_len(2);
+/// assert_eq!(dump.to_string(), "00 00 ... (+6 bytes)");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct HexDump<'a> {
+    data: &'a [u8],
+    compact: bool,
+    max_len: usize,
+    marker: &'a str,
+}
+
+impl<'a> HexDump<'a> {
+    pub fn new(data: &'a [u8]) -> Self {
+        Self { data, compact: false, max_len: DEFAULT_MAX_LEN, marker: "..." }
+    }
+
+    /// Puts the hex bytes on a single line, without offsets or ASCII.
+    pub fn compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    /// Sets how many bytes are shown before the dump is truncated.
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Sets the text that marks a truncated dump, `...` by default.
+    pub fn marker(mut self, marker: &'a str) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    fn write_compact(&self, f: &mut fmt::Formatter<'_>, shown: &[u8]) -> fmt::Result {
+        for (i, byte) in shown.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+
+    fn write_rows(&self, f: &mut fmt::Formatter<'_>, shown: &[u8]) -> fmt::Result {
+        for (row, chunk) in shown.chunks(BYTES_PER_ROW).enumerate() {
+            write!(f, "\n{:08x} ", row * BYTES_PER_ROW)?;
+            for i in 0..BYTES_PER_ROW {
+                if i == BYTES_PER_ROW / 2 {
+                    f.write_str(" ")?;
+                }
+                match chunk.get(i) {
+                    Some(byte) => write!(f, " {byte:02x}")?,
+                    None => f.write_str("   ")?,
+                }
+            }
+            f.write_str("  |")?;
+            for &byte in chunk {
+                let c = if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' };
+                write!(f, "{c}")?;
+            }
+            f.write_str("|")?;
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for HexDump<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let shown = &self.data[..self.data.len().min(self.max_len)];
+        let left_out = self.data.len() - shown.len();
+
+        if self.compact {
+            self.write_compact(f, shown)?;
+            if left_out > 0 {
+                let sep = if shown.is_empty() { "" } else { " " };
+                write!(f, "{sep}{} (+{left_out} bytes)", self.marker)?;
+            }
+        } else {
+            self.write_rows(f, shown)?;
+            if left_out > 0 {
+                write!(f, "\n{} (+{left_out} bytes)", self.marker)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::LogCapture;
+    use crate::Level;
+    use std::cell::Cell;
+    use tracing::level_filters::LevelFilter;
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_rows() {
+        let data = b"Hello, world!\n\x00\xff\x01";
+        assert_eq!(
+            HexDump::new(data).to_string(),
+            "\n00000000  48 65 6c 6c 6f 2c 20 77  6f 72 6c 64 21 0a 00 ff  |Hello, world!...|\
+             \n00000010  01                                                |.|"
+        );
+        assert_eq!(HexDump::new(b"").to_string(), "");
+
+        let data = [0x41u8; 40];
+        let dump = HexDump::new(&data).max_len(20).marker("[truncated]").to_string();
+        assert_eq!(dump.lines().count(), 4, "{dump}");
+        assert!(dump.ends_with("|AAAA|\n[truncated] (+20 bytes)"), "{dump}");
+    }
+
+    #[test]
+    fn test_compact() {
+        assert_eq!(HexDump::new(&[0xde, 0xad, 0xbe, 0xef]).compact(true).to_string(), "de ad be ef");
+        assert_eq!(HexDump::new(&[1, 2, 3]).compact(true).max_len(0).to_string(), "... (+3 bytes)");
+    }
+
+    #[test]
+    fn test_macro() {
+        let capture = LogCapture::start();
+        let frame = vec![0x02, 0x10, 0x03];
+        crate::hexdump!(Level::DEBUG, frame, "rx frame", compact = true);
+        crate::hexdump!(target: "can", Level::INFO, &frame[..2], "tx frame", compact = true, max_len = 1);
+
+        let records = capture.records();
+        assert_eq!(records[0].message, "rx frame");
+        assert_eq!(records[0].fields, [("len", "3".to_string()), ("hex", "02 10 03".to_string())]);
+        assert_eq!(records[1].target, "can");
+        assert_eq!(records[1].fields[1].1, "02 ... (+1 bytes)");
+    }
+
+    #[test]
+    fn test_macro_disabled() {
+        let evaluated = Cell::new(false);
+        let data = || {
+            evaluated.set(true);
+            [0u8; 4]
+        };
+        let subscriber = tracing_subscriber::registry().with(LevelFilter::INFO);
+        tracing::subscriber::with_default(subscriber, || crate::hexdump!(Level::DEBUG, data(), "skipped"));
+        assert!(!evaluated.get(), "The buffer should not be touched when the level is disabled");
+    }
+}
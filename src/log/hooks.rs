@@ -0,0 +1,219 @@
+use crossbeam::channel::{Receiver, Sender};
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::thread;
+use tracing::field::{Field, Visit};
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// Records waiting for the hook thread; further records are dropped when it is full.
+const QUEUE_SIZE: usize = 1024;
+
+type Hook = Arc<dyn Fn(&LogRecord) + Send + Sync>;
+
+static HOOKS: RwLock<Vec<(Level, Hook)>> = RwLock::new(Vec::new());
+static HOOK_QUEUE: OnceLock<Sender<LogRecord>> = OnceLock::new();
+
+/// Least severe level any hook is interested in, as [`level_rank`]; 0 means no hooks.
+static MAX_HOOK_RANK: AtomicUsize = AtomicUsize::new(0);
+
+/// Records dropped because the hook queue was full.
+static DROPPED: AtomicU64 = AtomicU64::new(0);
+
+/// A structured copy of an event, handed to the callbacks registered with [`on_event`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogRecord {
+    pub level: Level,
+    pub target: String,
+    pub message: String,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+/// Registers `hook` to be called for every event at `level` or more severe.
+///
+/// Hooks run on a dedicated `log-hooks` thread, never on the thread that logged,
+/// and only see events that pass the logger's filter. If the hooks fall behind
+/// by more than 1024 records, further records are dropped and counted in
+/// [`LogStats::hook_dropped`](super::LogStats::hook_dropped). A panicking hook
+/// does not affect the others.
+///
+/// # Examples
+///
+/// ```no_run
+/// use alumy::Level;
+///
+/// alumy::log::on_event(Level::ERROR, |record| {
+///     eprintln!("alert: {} {}", record.target, record.message);
+/// });
+/// ```
+pub fn on_event(level: Level, hook: impl Fn(&LogRecord) + Send + Sync + 'static) {
+    HOOK_QUEUE.get_or_init(spawn_hook_thread);
+
+    let mut hooks = HOOKS.write().unwrap_or_else(|e| e.into_inner());
+    hooks.push((level, Arc::new(hook)));
+    MAX_HOOK_RANK.fetch_max(level_rank(level), Ordering::Release);
+}
+
+fn level_rank(level: Level) -> usize {
+    match level {
+        Level::ERROR => 1,
+        Level::WARN => 2,
+        Level::INFO => 3,
+        Level::DEBUG => 4,
+        Level::TRACE => 5,
+    }
+}
+
+fn spawn_hook_thread() -> Sender<LogRecord> {
+    let (sender, receiver) = crossbeam::channel::bounded(QUEUE_SIZE);
+    thread::Builder::new()
+        .name("log-hooks".to_string())
+        .spawn(move || run_hooks(receiver))
+        .expect("Failed to spawn log hook thread");
+    sender
+}
+
+fn run_hooks(receiver: Receiver<LogRecord>) {
+    for record in receiver {
+        // Called without the lock, so a hook may register further hooks.
+        let hooks: Vec<Hook> = HOOKS
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .iter()
+            .filter(|(level, _)| record.level <= *level)
+            .map(|(_, hook)| hook.clone())
+            .collect();
+        for hook in hooks {
+            let _ = catch_unwind(AssertUnwindSafe(|| hook(&record)));
+        }
+    }
+}
+
+/// Layer that forwards events to the registered hooks.
+pub(crate) struct HookLayer;
+
+impl<S: Subscriber> Layer<S> for HookLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let level = *event.metadata().level();
+        if level_rank(level) > MAX_HOOK_RANK.load(Ordering::Acquire) {
+            return;
+        }
+        let Some(queue) = HOOK_QUEUE.get() else {
+            return;
+        };
+
+        let mut record = LogRecord {
+            level,
+            target: event.metadata().target().to_string(),
+            message: String::new(),
+            fields: Vec::new(),
+        };
+        event.record(&mut RecordVisitor(&mut record));
+        if queue.try_send(record).is_err() && DROPPED.fetch_add(1, Ordering::Relaxed) == 0 {
+            eprintln!("alumy: log hook queue is full, dropping records");
+        }
+    }
+}
+
+/// Returns the number of records dropped because the hooks fell behind.
+pub(crate) fn dropped() -> u64 {
+    DROPPED.load(Ordering::Relaxed)
+}
+
+pub(crate) struct RecordVisitor<'a>(pub(crate) &'a mut LogRecord);
+
+impl Visit for RecordVisitor<'_> {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.0.message = value.to_string();
+        } else {
+            self.0.fields.push((field.name(), value.to_string()));
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.0.message = format!("{value:?}");
+        } else {
+            self.0.fields.push((field.name(), format!("{value:?}")));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tracing_subscriber::prelude::*;
+
+    /// The hook thread is shared; a test filling its queue must not starve the others.
+    static SERIAL: Mutex<()> = Mutex::new(());
+
+    #[test]
+    fn test_hooks_receive_matching_events() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        on_event(Level::WARN, move |record| {
+            if record.target == "hooks_test" {
+                let _ = sender.send(record.clone());
+            }
+        });
+
+        let subscriber = tracing_subscriber::registry().with(HookLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(target: "hooks_test", "ignored");
+            tracing::error!(target: "hooks_test", code = 7, path = "/dev/ttyS0", "device failed");
+        });
+
+        let record = receiver.recv_timeout(Duration::from_secs(1)).unwrap();
+        assert_eq!(record.level, Level::ERROR);
+        assert_eq!(record.message, "device failed");
+        assert_eq!(record.fields, [("code", "7".to_string()), ("path", "/dev/ttyS0".to_string())]);
+        assert!(receiver.recv_timeout(Duration::from_millis(100)).is_err());
+    }
+
+    #[test]
+    fn test_hook_can_register_hooks() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        on_event(Level::ERROR, move |record| {
+            if record.target == "hooks_nested" {
+                let _ = sender.send(());
+                let sender = sender.clone();
+                on_event(Level::ERROR, move |_| {
+                    let _ = sender.send(());
+                });
+            }
+        });
+
+        let subscriber = tracing_subscriber::registry().with(HookLayer);
+        tracing::subscriber::with_default(subscriber, || tracing::error!(target: "hooks_nested", "first"));
+        assert!(receiver.recv_timeout(Duration::from_secs(1)).is_ok(), "Hook registering a hook deadlocked");
+    }
+
+    #[test]
+    fn test_full_queue_is_counted() {
+        let _serial = SERIAL.lock().unwrap_or_else(|e| e.into_inner());
+        let (gate, blocked) = crossbeam::channel::bounded::<()>(0);
+        on_event(Level::ERROR, move |record| {
+            if record.target == "hooks_full" {
+                let _ = blocked.recv();
+            }
+        });
+
+        let before = dropped();
+        let subscriber = tracing_subscriber::registry().with(HookLayer);
+        tracing::subscriber::with_default(subscriber, || {
+            for i in 0..QUEUE_SIZE + 100 {
+                tracing::error!(target: "hooks_full", i);
+            }
+        });
+        assert!(dropped() > before);
+        assert!(crate::log::stats().hook_dropped > before);
+        drop(gate);
+    }
+}
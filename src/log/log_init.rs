@@ -1,21 +1,64 @@
 use anyhow::{bail, Result};
-use rolling_file::{BasicRollingFileAppender, RollingConditionBasic};
 use std::fs::create_dir_all;
-use std::io::Write;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
-use std::thread::{self, JoinHandle};
-use tracing_subscriber::fmt::MakeWriter;
+use std::time::Duration;
+use tracing::level_filters::LevelFilter;
+use tracing_log::AsLog;
 use tracing_subscriber::prelude::*;
-use tracing_subscriber::{fmt, EnvFilter};
-
-type SharedSender = Arc<Mutex<Option<crossbeam::channel::Sender<Vec<u8>>>>>;
+use tracing_subscriber::{fmt, reload, EnvFilter, Layer, Registry};
+
+use super::conf;
+use super::audit::{audit_header, AuditBypass, AuditKey, AuditRoute, AuditSink, Chain};
+use super::format::{file_header, HeaderHook, Identity, LogFormat, Stamped, UptimeTime};
+use super::binary::{binary_header, frames_to_text, note, BinaryLayer, BinarySink, Tables};
+use super::circular::CircularFile;
+use super::correlation::CorrelationLayer;
+use super::hooks::HookLayer;
+use super::logfmt::{Logfmt, LogfmtFields};
+#[cfg(feature = "encryption")]
+use super::encryption::EncryptionKey;
+#[cfg(feature = "otlp")]
+use super::otlp::{OtlpConfig, OtlpGuard};
+use super::file::{FileMode, RecordCodec, ReopenFile, ResilientWriter};
+use super::rolling::{RollingFile, RollingOptions, RotationNaming};
+#[cfg(unix)]
+use super::shared::SharedFile;
+use super::stats::{self, Stats};
+use super::theme::{console_ansi, Theme, Themed};
+use super::writer::{Durability, FlushPolicy, NonBlockingGuard, NonBlockingWriter, Sink};
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// The logger installed by [`logger_init`], kept so that it can be reloaded.
+struct Installed {
+    /// The resolved configuration in effect.
+    config: LogConfig,
+    handle: reload::Handle<BoxedLayer, Registry>,
+    writer: Option<NonBlockingWriter>,
+    guard: Option<NonBlockingGuard>,
+    audit: Option<NonBlockingWriter>,
+    _audit_guard: Option<NonBlockingGuard>,
+    #[cfg(feature = "otlp")]
+    _otlp_guard: Option<OtlpGuard>,
+    /// Stops watching the config file once this logger is replaced.
+    _watch: Option<conf::Watch>,
+}
 
-static LOG_GUARD: std::sync::OnceLock<Arc<NonBlockingGuard>> = std::sync::OnceLock::new();
+static INSTALLED: Mutex<Option<Installed>> = Mutex::new(None);
+/// Serializes reloads, which open new sinks without holding [`INSTALLED`].
+static RELOADING: Mutex<()> = Mutex::new(());
 
 /// Configuration for the logger.
 /// 
 /// Supports fluent API for easy setup.
+///
+/// Each setting is taken from the first source that provides it:
+///
+/// 1. an explicit builder call, such as [`with_filter`](Self::with_filter) or [`with_file`](Self::with_file);
+/// 2. the environment, if enabled with [`with_env`](Self::with_env);
+/// 3. the file given to [`with_config_file`](Self::with_config_file);
+/// 4. the level given to [`new`](Self::new), or the built-in default.
 /// 
 /// # Examples
 /// 
@@ -43,6 +86,28 @@ pub struct LogConfig {
     pub display_thread_name: Option<bool>,
     pub display_thread_id: Option<bool>,
     pub time_format: Option<String>,
+    pub flush_policy: Option<FlushPolicy>,
+    pub file_mode: Option<FileMode>,
+    pub log_bridge: Option<bool>,
+    pub version: Option<String>,
+    pub display_identity: Option<bool>,
+    pub display_header: Option<bool>,
+    pub header_hook: Option<HeaderHook>,
+    pub durability: Option<Durability>,
+    pub format: Option<LogFormat>,
+    pub rotation_naming: Option<RotationNaming>,
+    pub audit_target: Option<String>,
+    pub audit_file: Option<String>,
+    pub audit_key: Option<AuditKey>,
+    pub env_overrides: Option<bool>,
+    pub config_file: Option<String>,
+    pub watch_config: Option<bool>,
+    pub theme: Option<Theme>,
+    pub clock_watch: Option<Duration>,
+    #[cfg(feature = "otlp")]
+    pub otlp: Option<OtlpConfig>,
+    #[cfg(feature = "encryption")]
+    pub encryption: Option<EncryptionKey>,
 }
 
 impl LogConfig {
@@ -70,11 +135,34 @@ impl LogConfig {
     }
 
     /// Enables or disables ANSI colors.
+    ///
+    /// By default the console is colored only when stdout is a terminal, and
+    /// log files are not colored. A non-empty `NO_COLOR` environment variable
+    /// turns console colors off and `CLICOLOR_FORCE=1` turns them on, whatever
+    /// is set here.
     pub fn with_ansi(mut self, enable: bool) -> Self {
         self.ansi = Some(enable);
         self
     }
 
+    /// Sets the colors of the level names, target and timestamp when ANSI colors are enabled (see [`Theme`]).
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    /// Logs a `WARN` event on the `alumy` target whenever the wall clock is set
+    /// by more than `threshold`, e.g. by NTP or an RTC sync.
+    ///
+    /// The event reads `Clock jumped back by 5s` with the fields `jump_ms`
+    /// (negative when set back) and `uptime_ms`, so records on either side of
+    /// the jump can be put in order. See [`on_clock_jump`](crate::sys::clock::on_clock_jump)
+    /// to react to jumps in code.
+    pub fn with_clock_watch(mut self, threshold: Duration) -> Self {
+        self.clock_watch = Some(threshold);
+        self
+    }
+
     /// Enables or disables displaying the target (module path).
     pub fn with_target(mut self, enable: bool) -> Self {
         self.display_target = Some(enable);
@@ -99,6 +187,12 @@ impl LogConfig {
         self
     }
 
+    /// Sets the layout of each record (see [`LogFormat`]).
+    pub fn with_format(mut self, format: LogFormat) -> Self {
+        self.format = Some(format);
+        self
+    }
+
     /// Enables or disables displaying thread names.
     pub fn with_thread_name(mut self, enable: bool) -> Self {
         self.display_thread_name = Some(enable);
@@ -111,119 +205,175 @@ impl LogConfig {
         self
     }
 
-    /// Initializes the global logger with this configuration.
-    pub fn init(&self) -> Result<()> {
-        logger_init(self)
+    /// Sets when the background writer flushes the log file (see [`FlushPolicy`]).
+    pub fn with_flush_policy(mut self, policy: FlushPolicy) -> Self {
+        self.flush_policy = Some(policy);
+        self
     }
 
-    // Helper methods for internal use
-    fn display_target(&self) -> bool { self.display_target.unwrap_or(false) }
-    fn display_level(&self) -> bool { self.display_level.unwrap_or(true) }
-    fn display_time(&self) -> bool { self.display_time.unwrap_or(true) }
-    fn display_thread_name(&self) -> bool { self.display_thread_name.unwrap_or(false) }
-    fn display_thread_id(&self) -> bool { self.display_thread_id.unwrap_or(false) }
-    fn time_format(&self) -> &str { self.time_format.as_deref().unwrap_or("iso") }
-}
+    /// Sets when written log data is forced to storage with `fdatasync` (see [`Durability`]).
+    pub fn with_durability(mut self, durability: Durability) -> Self {
+        self.durability = Some(durability);
+        self
+    }
 
-struct UptimeTime;
+    /// Sets how rotated log files are named in [`FileMode::Rolling`] (see [`RotationNaming`]).
+    pub fn with_rotation_naming(mut self, naming: RotationNaming) -> Self {
+        self.rotation_naming = Some(naming);
+        self
+    }
 
-impl tracing_subscriber::fmt::time::FormatTime for UptimeTime {
-    fn format_time(&self, w: &mut tracing_subscriber::fmt::format::Writer<'_>) -> std::fmt::Result {
-        let duration = crate::sys::uptime::uptime_duration();
-        write!(w, "[{:>6}.{:03}]", duration.as_secs(), duration.subsec_millis())
+    /// Writes events with the target `target` (or below it, e.g. `audit::login`)
+    /// to a separate audit file at `path`, hash-chained with `key` (see [`audit`](super::audit)).
+    ///
+    /// Audit events bypass the filter directives for their target and are left
+    /// out of the regular log. They still have to be at a level enabled for some
+    /// target, as `tracing` skips anything more verbose than that.
+    /// The audit file rotates with the configured max size, max files and
+    /// [`RotationNaming`], and is flushed after every event.
+    pub fn with_audit(mut self, target: impl Into<String>, path: impl Into<String>, key: AuditKey) -> Self {
+        self.audit_target = Some(target.into());
+        self.audit_file = Some(path.into());
+        self.audit_key = Some(key);
+        self
     }
-}
 
-pub struct NonBlockingWriter {
-    sender: SharedSender,
-}
+    /// Enables or disables overrides from the environment (default: disabled).
+    ///
+    /// With the app name `my-app`, `MY_APP_LOG` (or else `RUST_LOG`) replaces the
+    /// filter, `MY_APP_LOG_FILE` the log file and `MY_APP_LOG_FORMAT` the
+    /// format (`text`, `logfmt` or `binary`). They take precedence over the
+    /// config file, but not over explicit builder calls.
+    pub fn with_env(mut self, enable: bool) -> Self {
+        self.env_overrides = Some(enable);
+        self
+    }
 
-impl NonBlockingWriter {
-    pub fn new<W: Write + Send + 'static>(mut writer: W) -> (Self, NonBlockingGuard) {
-        let (sender, receiver) = crossbeam::channel::unbounded::<Vec<u8>>();
-        let shared_sender: SharedSender = Arc::new(Mutex::new(Some(sender)));
-
-        let handle = thread::Builder::new()
-            .name("tracing-writer".to_string())
-            .stack_size(2 * 1024 * 1024)
-            .spawn(move || {
-                for msg in receiver {
-                    let _ = writer.write_all(&msg);
-                    let _ = writer.flush();
-                }
-            })
-            .expect("Failed to spawn logging thread");
-
-        let guard = NonBlockingGuard {
-            sender: shared_sender.clone(),
-            handle: Some(handle),
-        };
-
-        (NonBlockingWriter { sender: shared_sender }, guard)
+    /// Reads settings from a `key = value` config file, such as
+    /// `/etc/my-app/my-app.conf`, when the logger is initialized.
+    ///
+    /// Keys are the field names of [`LogConfig`], e.g. `level = debug` or
+    /// `max_size = 10M`. Unknown keys and invalid values are errors.
+    pub fn with_config_file(mut self, path: impl Into<String>) -> Self {
+        self.config_file = Some(path.into());
+        self
     }
-}
 
-impl Clone for NonBlockingWriter {
-    fn clone(&self) -> Self {
-        Self {
-            sender: self.sender.clone(),
-        }
+    /// Reads settings from the config file at `path`, like
+    /// [`with_config_file`](Self::with_config_file), and applies them again
+    /// whenever the file changes.
+    ///
+    /// The level, filter, format flags and file settings are reloaded without a
+    /// restart; settings made with explicit builder calls keep taking precedence.
+    /// An invalid file is reported as an `ERROR` event and the previous
+    /// configuration stays in place. The audit trail is not reloaded.
+    ///
+    /// ```no_run
+    /// use alumy::{build_path, LogConfig};
+    ///
+    /// LogConfig::new("my-app", "info")
+    ///     .watch(build_path!("/etc", "my-app", ".conf"))
+    ///     .init()
+    ///     .unwrap();
+    /// ```
+    pub fn watch(mut self, path: impl Into<String>) -> Self {
+        self.config_file = Some(path.into());
+        self.watch_config = Some(true);
+        self
     }
-}
 
-pub struct NonBlockingWriterHandle {
-    sender: SharedSender,
-    buffer: Vec<u8>,
-}
+    /// Also exports events and spans to an OpenTelemetry collector over OTLP/HTTP (see [`OtlpConfig`]).
+    ///
+    /// The name and version are sent as the `service.name` and `service.version`
+    /// resource attributes. Requires the `otlp` feature.
+    #[cfg(feature = "otlp")]
+    pub fn with_otlp(mut self, config: OtlpConfig) -> Self {
+        self.otlp = Some(config);
+        self
+    }
 
-impl Write for NonBlockingWriterHandle {
-    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        self.buffer.extend_from_slice(buf);
-        Ok(buf.len())
+    /// Encrypts the log file with ChaCha20-Poly1305 under `key` (see [`EncryptionKey`]).
+    ///
+    /// Every newly opened file, rotated or reopened after a restart, starts a
+    /// new stream with its own key. Read the files back with
+    /// [`decrypt_file`](super::decrypt_file). Requires the rolling file mode and
+    /// the `encryption` feature; the audit file is not encrypted.
+    #[cfg(feature = "encryption")]
+    pub fn with_encryption(mut self, key: EncryptionKey) -> Self {
+        self.encryption = Some(key);
+        self
     }
 
-    fn flush(&mut self) -> std::io::Result<()> {
-        if !self.buffer.is_empty() {
-            if let Ok(guard) = self.sender.lock() {
-                if let Some(ref sender) = *guard {
-                    let _ = sender.send(std::mem::take(&mut self.buffer));
-                }
-            }
-        }
-        Ok(())
+    /// Sets how the log file is written and rotated (see [`FileMode`]).
+    pub fn with_file_mode(mut self, mode: FileMode) -> Self {
+        self.file_mode = Some(mode);
+        self
     }
-}
 
-impl Drop for NonBlockingWriterHandle {
-    fn drop(&mut self) {
-        let _ = self.flush();
+    /// Enables or disables forwarding records from the `log` crate (default: disabled).
+    ///
+    /// Forwarded records go through the same filter and sinks as `tracing` events,
+    /// and the `log` max level follows the configured level or filter.
+    pub fn with_log_bridge(mut self, enable: bool) -> Self {
+        self.log_bridge = Some(enable);
+        self
     }
-}
 
-impl<'a> MakeWriter<'a> for NonBlockingWriter {
-    type Writer = NonBlockingWriterHandle;
+    /// Sets the application version reported in log records (defaults to the alumy version).
+    pub fn with_version(mut self, version: impl Into<String>) -> Self {
+        self.version = Some(version.into());
+        self
+    }
 
-    fn make_writer(&'a self) -> Self::Writer {
-        NonBlockingWriterHandle {
-            sender: self.sender.clone(),
-            buffer: Vec::with_capacity(256),
-        }
+    /// Enables or disables stamping each record with the app name, version, hostname, PID and boot ID.
+    pub fn with_identity(mut self, enable: bool) -> Self {
+        self.display_identity = Some(enable);
+        self
     }
-}
 
-pub struct NonBlockingGuard {
-    sender: SharedSender,
-    handle: Option<JoinHandle<()>>,
-}
+    /// Enables or disables the header written at the top of each new log file.
+    ///
+    /// The header holds the app identity, build info, start time, uptime and this
+    /// configuration. It applies to [`FileMode::Rolling`] only.
+    pub fn with_header(mut self, enable: bool) -> Self {
+        self.display_header = Some(enable);
+        self
+    }
 
-impl Drop for NonBlockingGuard {
-    fn drop(&mut self) {
-        if let Ok(mut guard) = self.sender.lock() {
-            *guard = None;
-        }
-        if let Some(handle) = self.handle.take() {
-            let _ = handle.join();
-        }
+    /// Appends the output of `hook` to every file header, and enables the header.
+    pub fn with_header_hook(mut self, hook: impl Fn() -> String + Send + Sync + 'static) -> Self {
+        self.display_header = Some(true);
+        self.header_hook = Some(HeaderHook::new(hook));
+        self
+    }
+
+    /// Initializes the global logger with this configuration.
+    pub fn init(&self) -> Result<()> {
+        logger_init(self)
+    }
+
+    // Helper methods for internal use
+    pub(crate) fn display_target(&self) -> bool { self.display_target.unwrap_or(false) }
+    pub(crate) fn display_level(&self) -> bool { self.display_level.unwrap_or(true) }
+    pub(crate) fn display_time(&self) -> bool { self.display_time.unwrap_or(true) }
+    pub(crate) fn display_thread_name(&self) -> bool { self.display_thread_name.unwrap_or(false) }
+    pub(crate) fn display_thread_id(&self) -> bool { self.display_thread_id.unwrap_or(false) }
+    pub(crate) fn time_format(&self) -> &str { self.time_format.as_deref().unwrap_or("iso") }
+    pub(crate) fn format(&self) -> LogFormat { self.format.unwrap_or_default() }
+    fn flush_policy(&self) -> FlushPolicy { self.flush_policy.unwrap_or_default() }
+    pub(crate) fn file_mode(&self) -> FileMode { self.file_mode.unwrap_or_default() }
+    fn rotation_naming(&self) -> RotationNaming { self.rotation_naming.unwrap_or_default() }
+    fn durability(&self) -> Durability { self.durability.unwrap_or_default() }
+    fn log_bridge(&self) -> bool { self.log_bridge.unwrap_or(false) }
+    fn display_identity(&self) -> bool { self.display_identity.unwrap_or(false) }
+    fn display_header(&self) -> bool { self.display_header.unwrap_or(false) }
+    fn watch_config(&self) -> bool { self.watch_config.unwrap_or(false) }
+    fn audit_target(&self) -> Option<&str> { self.audit_target.as_deref().filter(|_| self.audit_file.is_some()) }
+    pub(crate) fn max_files(&self) -> usize { self.max_files.unwrap_or(5).max(2) as usize }
+    pub(crate) fn max_size(&self) -> u64 {
+        self.max_size.as_deref()
+            .and_then(crate::fs::filesize::parse_size)
+            .unwrap_or(1024 * 1024)
     }
 }
 
@@ -236,7 +386,22 @@ fn log_config_check(log_config: &LogConfig) -> Result<()> {
         bail!("Log level is required");
     }
 
-    if log_config.file.is_some() {
+    if log_config.format() == LogFormat::Binary
+        && (log_config.file.is_none() || log_config.file_mode() != FileMode::Rolling)
+    {
+        bail!("Binary log format requires a log file in rolling mode");
+    }
+
+    if log_config.audit_file.is_some() && log_config.audit_key.is_none() {
+        bail!("Audit log requires a key");
+    }
+
+    #[cfg(feature = "encryption")]
+    if log_config.encryption.is_some() && (log_config.file.is_none() || log_config.file_mode() != FileMode::Rolling) {
+        bail!("Log encryption requires a log file in rolling mode");
+    }
+
+    if log_config.file.is_some() && log_config.file_mode() != FileMode::Reopen {
         if log_config.max_size.is_none() {
             bail!("Log max size is required");
         }
@@ -246,6 +411,13 @@ fn log_config_check(log_config: &LogConfig) -> Result<()> {
         }
     }
 
+    if log_config.file.is_some()
+        && log_config.file_mode() == FileMode::Circular
+        && log_config.max_size() < super::circular::MIN_SIZE
+    {
+        bail!("Circular log file must be at least {} bytes", super::circular::MIN_SIZE);
+    }
+
     Ok(())
 }
 
@@ -262,81 +434,482 @@ fn log_dir_create(log_config: &LogConfig) -> Result<()> {
     Ok(())
 }
 
-macro_rules! subscriber_init {
-    ($env_filter:expr, $layer:expr, $cfg:expr) => {
-        let registry = tracing_subscriber::registry().with($env_filter);
-        if !$cfg.display_time() {
-            let _ = registry.with($layer.without_time()).try_init();
+/// Installs `subscriber` as the global default and, if enabled, the `log` bridge.
+///
+/// Returns `false` if a global subscriber was already set.
+fn subscriber_install<S>(subscriber: S, log_config: &LogConfig) -> bool
+where
+    S: tracing::Subscriber + Send + Sync + 'static,
+{
+    if tracing::subscriber::set_global_default(subscriber).is_err() {
+        return false;
+    }
+
+    if log_config.log_bridge() {
+        let _ = tracing_log::LogTracer::builder()
+            .with_max_level(bridge_level(log_config))
+            .init();
+    }
+    true
+}
+
+pub(crate) fn env_filter(log_config: &LogConfig) -> EnvFilter {
+    log_config
+        .filter
+        .as_deref()
+        .filter(|s| !s.is_empty())
+        .map(|f| EnvFilter::try_new(f).unwrap_or_else(|_| EnvFilter::new("info")))
+        .unwrap_or_else(|| EnvFilter::new(log_config.level.as_deref().unwrap_or("info")))
+}
+
+/// Returns the layer writing audit events to `writer`, if auditing is enabled.
+fn audit_layer(log_config: &LogConfig, writer: Option<NonBlockingWriter>) -> Option<BoxedLayer> {
+    let target = log_config.audit_target()?;
+    let layer = fmt::layer()
+        .with_writer(writer?)
+        .with_ansi(false)
+        .with_target(true)
+        .with_timer(fmt::time::LocalTime::new(time::macros::format_description!(
+            "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+        )));
+    Some(AuditRoute::audit(layer, target).boxed())
+}
+
+macro_rules! fmt_layer {
+    ($layer:expr, $ansi:expr, $cfg:expr) => {{
+        let stamp = $cfg.display_identity().then(|| Identity::new($cfg).to_fields());
+        let format = fmt::format()
+            .with_target($cfg.display_target())
+            .with_level($cfg.display_level())
+            .with_thread_names($cfg.display_thread_name())
+            .with_thread_ids($cfg.display_thread_id());
+        let audit = $cfg.audit_target();
+        if $cfg.format() == LogFormat::Logfmt {
+            let format = Stamped::new(Logfmt::new($cfg), stamp);
+            AuditRoute::regular($layer.fmt_fields(LogfmtFields).event_format(format), audit).boxed()
+        } else if $ansi {
+            let format = Stamped::new(Themed::new($cfg), stamp);
+            AuditRoute::regular($layer.event_format(format), audit).boxed()
+        } else if !$cfg.display_time() {
+            let format = Stamped::new(format.without_time(), stamp);
+            AuditRoute::regular($layer.event_format(format), audit).boxed()
         } else if $cfg.time_format() == "uptime" {
-            let _ = registry.with($layer.with_timer(UptimeTime)).try_init();
+            let format = Stamped::new(format.with_timer(UptimeTime), stamp);
+            AuditRoute::regular($layer.event_format(format), audit).boxed()
         } else {
-            let _ = registry
-                .with($layer.with_timer(fmt::time::LocalTime::new(time::macros::format_description!(
+            let format = Stamped::new(
+                format.with_timer(fmt::time::LocalTime::new(time::macros::format_description!(
                     "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
-                ))))
-                .try_init();
+                ))),
+                stamp,
+            );
+            AuditRoute::regular($layer.event_format(format), audit).boxed()
+        }
+    }};
+}
+
+/// Returns the max level of the `log` bridge, which follows the configured level or filter.
+fn bridge_level(log_config: &LogConfig) -> tracing_log::log::LevelFilter {
+    env_filter(log_config).max_level_hint().unwrap_or(LevelFilter::TRACE).as_log()
+}
+
+/// Builds the filter and every layer for `log_config`, writing to `writer` or,
+/// without a log file, to stdout.
+fn layers(
+    log_config: &LogConfig,
+    writer: Option<NonBlockingWriter>,
+    audit: Option<NonBlockingWriter>,
+) -> Result<BoxedLayer> {
+    // Audit events bypass the level filter.
+    let filter = AuditBypass::new(env_filter(log_config), log_config.audit_target());
+
+    let output = match writer {
+        Some(writer) if log_config.format() == LogFormat::Binary => {
+            AuditRoute::regular(BinaryLayer::new(writer), log_config.audit_target()).boxed()
+        }
+        Some(writer) => {
+            // Files are colored only on request, and `NO_COLOR` applies to them too.
+            let ansi = log_config.ansi.unwrap_or(false) && console_ansi(Some(true));
+            let layer = fmt::layer()
+                .with_writer(writer)
+                .with_ansi(ansi);
+            fmt_layer!(layer, ansi, log_config)
+        }
+        None => {
+            let ansi = console_ansi(log_config.ansi);
+            let layer = fmt::layer()
+                .with_ansi(ansi);
+            fmt_layer!(layer, ansi, log_config)
         }
     };
+
+    let layer = filter
+        .and_then(CorrelationLayer)
+        .and_then(HookLayer)
+        .and_then(audit_layer(log_config, audit))
+        .and_then(output)
+        .boxed();
+    Ok(layer)
+}
+
+/// Returns the path of the active log file, `<dir>/<stem>.log`, for the rotating modes.
+fn rolling_log_path(log_config: &LogConfig, file: &str) -> PathBuf {
+    let file_path = Path::new(file);
+
+    let dir = file_path.parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+
+    let basename = file_path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_else(|| log_config.name.as_deref().unwrap_or("alumy"));
+
+    dir.join(format!("{basename}.log"))
+}
+
+fn rolling_sink(log_config: &LogConfig, file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let options = RollingOptions {
+        path: rolling_log_path(log_config, file),
+        max_size: log_config.max_size(),
+        max_files: log_config.max_files(),
+        naming: log_config.rotation_naming(),
+        header: log_config.display_header().then(|| file_header(log_config)),
+        #[cfg(feature = "encryption")]
+        encryption: log_config.encryption.clone(),
+    };
+    let log_path = options.path.clone();
+
+    let rolling_file = RollingFile::open(options.clone(), stats.clone())
+        .map_err(|e| anyhow::anyhow!("Failed to create rolling file appender: {e}"))?;
+
+    let open_file = {
+        let stats = stats.clone();
+        move || {
+            if let Some(parent) = options.path.parent() {
+                create_dir_all(parent)?;
+            }
+            RollingFile::open(options.clone(), stats.clone())
+        }
+    };
+
+    Ok(Box::new(
+        ResilientWriter::new(rolling_file, open_file)
+            .watch_path(log_path)
+            .with_stats(stats),
+    ))
+}
+
+fn binary_sink(log_config: &LogConfig, file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let tables = Arc::new(Mutex::new(Tables::default()));
+    let options = RollingOptions {
+        path: rolling_log_path(log_config, file),
+        max_size: log_config.max_size(),
+        max_files: log_config.max_files(),
+        naming: log_config.rotation_naming(),
+        header: Some(binary_header(tables.clone())),
+        #[cfg(feature = "encryption")]
+        encryption: log_config.encryption.clone(),
+    };
+    let log_path = options.path.clone();
+
+    let rolling_file = RollingFile::open(options.clone(), stats.clone())
+        .map_err(|e| anyhow::anyhow!("Failed to create rolling file appender: {e}"))?;
+
+    let open_file = {
+        let stats = stats.clone();
+        let tables = tables.clone();
+        move || {
+            if let Some(parent) = options.path.parent() {
+                create_dir_all(parent)?;
+            }
+            RollingFile::open(options.clone(), stats.clone()).map(|f| BinarySink::new(f, tables.clone()))
+        }
+    };
+
+    Ok(Box::new(
+        ResilientWriter::new(BinarySink::new(rolling_file, tables), open_file)
+            .watch_path(log_path)
+            .with_stats(stats)
+            .with_codec(RecordCodec {
+                to_text: frames_to_text,
+                notice: note,
+            }),
+    ))
+}
+
+fn audit_sink(log_config: &LogConfig, file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let path = Path::new(file).to_path_buf();
+    if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+        create_dir_all(parent)?;
+    }
+
+    let Some(key) = log_config.audit_key.clone() else {
+        bail!("Audit log requires a key");
+    };
+    let chain = Arc::new(Mutex::new(
+        Chain::resume(&path, key).map_err(|e| anyhow::anyhow!("Failed to read audit log: {e}"))?,
+    ));
+    let options = RollingOptions {
+        path: path.clone(),
+        max_size: log_config.max_size(),
+        max_files: log_config.max_files(),
+        naming: log_config.rotation_naming(),
+        header: Some(audit_header(chain.clone())),
+        #[cfg(feature = "encryption")]
+        encryption: None,
+    };
+
+    let rolling_file = RollingFile::open(options.clone(), stats.clone())
+        .map_err(|e| anyhow::anyhow!("Failed to create audit log file: {e}"))?;
+
+    let open_file = {
+        let stats = stats.clone();
+        let chain = chain.clone();
+        move || {
+            if let Some(parent) = options.path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                create_dir_all(parent)?;
+            }
+            RollingFile::open(options.clone(), stats.clone()).map(|f| AuditSink::new(f, chain.clone()))
+        }
+    };
+
+    Ok(Box::new(
+        ResilientWriter::new(AuditSink::new(rolling_file, chain), open_file)
+            .watch_path(path)
+            .with_stats(stats),
+    ))
+}
+
+#[cfg(unix)]
+fn shared_sink(log_config: &LogConfig, file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let log_path = rolling_log_path(log_config, file);
+    let max_size = log_config.max_size();
+    let max_files = log_config.max_files();
+
+    let shared_file = SharedFile::open(&log_path, max_size, max_files)
+        .map(|f| f.with_stats(stats.clone()))
+        .map_err(|e| anyhow::anyhow!("Failed to open shared log file: {e}"))?;
+
+    let open_file = {
+        let stats = stats.clone();
+        move || {
+            if let Some(parent) = log_path.parent() {
+                create_dir_all(parent)?;
+            }
+            SharedFile::open(&log_path, max_size, max_files).map(|f| f.with_stats(stats.clone()))
+        }
+    };
+
+    Ok(Box::new(ResilientWriter::new(shared_file, open_file).with_stats(stats)))
+}
+
+#[cfg(not(unix))]
+fn shared_sink(_log_config: &LogConfig, _file: &str, _stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    bail!("Shared file mode is only supported on Unix");
+}
+
+fn circular_sink(log_config: &LogConfig, file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let path = Path::new(file).to_path_buf();
+    let size = log_config.max_size();
+    let circular_file = CircularFile::open(&path, size)
+        .map_err(|e| anyhow::anyhow!("Failed to open circular log file: {e}"))?;
+
+    let open_file = move || {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            create_dir_all(parent)?;
+        }
+        CircularFile::open(&path, size)
+    };
+
+    Ok(Box::new(ResilientWriter::new(circular_file, open_file).with_stats(stats)))
+}
+
+fn reopen_sink(file: &str, stats: Arc<Stats>) -> Result<Box<dyn Sink + Send>> {
+    let path = Path::new(file).to_path_buf();
+    let log_file = ReopenFile::open(&path)
+        .map(|f| f.with_stats(stats.clone()))
+        .map_err(|e| anyhow::anyhow!("Failed to open log file: {e}"))?;
+
+    #[cfg(unix)]
+    super::file::install_reopen_signal()
+        .map_err(|e| anyhow::anyhow!("Failed to install SIGHUP handler: {e}"))?;
+
+    let open_file = {
+        let stats = stats.clone();
+        move || {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                create_dir_all(parent)?;
+            }
+            ReopenFile::open(&path).map(|f| f.with_stats(stats.clone()))
+        }
+    };
+
+    Ok(Box::new(ResilientWriter::new(log_file, open_file).with_stats(stats)))
+}
+
+/// Opens the sink configured for the log file, if any, behind a background writer.
+fn file_writer(log_config: &LogConfig) -> Result<Option<(NonBlockingWriter, NonBlockingGuard)>> {
+    let Some(file) = log_config.file.as_deref() else {
+        return Ok(None);
+    };
+
+    let stats = Arc::new(Stats::default());
+    let sink = match log_config.file_mode() {
+        FileMode::Rolling if log_config.format() == LogFormat::Binary => binary_sink(log_config, file, stats.clone())?,
+        FileMode::Rolling => rolling_sink(log_config, file, stats.clone())?,
+        FileMode::Reopen => reopen_sink(file, stats.clone())?,
+        FileMode::Shared => shared_sink(log_config, file, stats.clone())?,
+        FileMode::Circular => circular_sink(log_config, file, stats.clone())?,
+    };
+
+    Ok(Some(NonBlockingWriter::spawn(sink, log_config.flush_policy(), log_config.durability(), stats)))
+}
+
+/// Opens the audit file, if enabled, behind a background writer that flushes every event.
+fn audit_writer(log_config: &LogConfig) -> Result<Option<(NonBlockingWriter, NonBlockingGuard)>> {
+    let (Some(_), Some(file)) = (log_config.audit_target(), log_config.audit_file.as_deref()) else {
+        return Ok(None);
+    };
+
+    let stats = Arc::new(Stats::default());
+    let sink = audit_sink(log_config, file, stats.clone())?;
+    Ok(Some(NonBlockingWriter::spawn(sink, FlushPolicy::EveryEvent, log_config.durability(), stats)))
+}
+
+/// The settings that require a new sink when they change on reload.
+#[allow(clippy::type_complexity)]
+fn sink_settings(
+    log_config: &LogConfig,
+) -> (Option<&str>, FileMode, u64, usize, bool, RotationNaming, FlushPolicy, Durability, bool, bool, Option<&str>) {
+    (
+        log_config.file.as_deref(),
+        log_config.file_mode(),
+        log_config.max_size(),
+        log_config.max_files(),
+        log_config.format() == LogFormat::Binary,
+        log_config.rotation_naming(),
+        log_config.flush_policy(),
+        log_config.durability(),
+        log_config.display_header(),
+        log_config.display_identity(),
+        log_config.version.as_deref(),
+    )
 }
 
 /// Initializes the global logger.
 /// 
 /// This is an internal function used by [`LogConfig::init`].
-pub(crate) fn logger_init(log_config: &LogConfig) -> Result<()> {
+pub(crate) fn logger_init(base: &LogConfig) -> Result<()> {
+    let log_config = &conf::resolve(base).inspect_err(|e| eprintln!("Failed to load log config: {e:#}"))?;
     log_config_check(log_config).inspect_err(|e| eprintln!("Failed to check log config: {e}"))?;
     log_dir_create(log_config).inspect_err(|e| eprintln!("Failed to create log directory: {e}"))?;
 
-    let env_filter = log_config
-        .filter
-        .as_deref()
-        .filter(|s| !s.is_empty())
-        .map(|f| EnvFilter::try_new(f).unwrap_or_else(|_| EnvFilter::new("info")))
-        .unwrap_or_else(|| EnvFilter::new(log_config.level.as_deref().unwrap_or("info")));
+    let (audit, audit_guard) = audit_writer(log_config)
+        .inspect_err(|e| eprintln!("Failed to open audit log: {e}"))?
+        .unzip();
+    let (writer, guard) = file_writer(log_config)?.unzip();
+
+    let layer = layers(log_config, writer.clone(), audit.clone())?;
+    let (layer, handle) = reload::Layer::new(layer);
+    let registry = tracing_subscriber::registry().with(layer);
+
+    // The exporter is outside the reloadable layers; it still follows the reloaded filter.
+    #[cfg(feature = "otlp")]
+    let (otlp, otlp_guard) = match &log_config.otlp {
+        Some(config) => {
+            let (layer, guard) = super::otlp::spawn(log_config, config)
+                .inspect_err(|e| eprintln!("Failed to start OTLP exporter: {e}"))?;
+            (Some(layer), Some(guard))
+        }
+        None => (None, None),
+    };
+    #[cfg(feature = "otlp")]
+    let registry = registry.with(otlp);
 
-    if let Some(file) = log_config.file.as_deref() {
-        let file_path = Path::new(file);
-        
-        let dir = file_path.parent()
-            .filter(|p| !p.as_os_str().is_empty())
-            .unwrap_or_else(|| Path::new("."));
-        
-        let basename = file_path.file_stem()
-            .and_then(|s| s.to_str())
-            .unwrap_or_else(|| log_config.name.as_deref().unwrap_or("alumy"));
+    if !subscriber_install(registry, log_config) {
+        return Ok(());
+    }
+    // Only the installed logger's writer may report stats.
+    if let Some(writer) = &writer {
+        stats::register(writer.clone());
+    }
 
-        let max_size = log_config.max_size.as_deref()
-            .and_then(crate::fs::filesize::parse_size)
-            .unwrap_or(1024 * 1024);
+    let watch = match (base.watch_config(), base.config_file.as_deref()) {
+        (true, Some(path)) => conf::watch(base.clone(), PathBuf::from(path)),
+        _ => None,
+    };
+    *INSTALLED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Installed {
+        config: log_config.clone(),
+        handle,
+        writer,
+        guard,
+        audit,
+        _audit_guard: audit_guard,
+        #[cfg(feature = "otlp")]
+        _otlp_guard: otlp_guard,
+        _watch: watch,
+    });
+    if let Some(threshold) = log_config.clock_watch {
+        crate::sys::clock::on_clock_jump(threshold, |jump| {
+            tracing::warn!(
+                target: "alumy",
+                jump_ms = jump.millis(),
+                uptime_ms = jump.uptime.as_millis() as u64,
+                "Clock jumped {jump}"
+            );
+        });
+    }
+    Ok(())
+}
 
-        let rolling_appender = BasicRollingFileAppender::new(
-            dir.join(format!("{basename}.log")),
-            RollingConditionBasic::new().max_size(max_size),
-            log_config.max_files.unwrap_or(5).max(2) as usize,
-        )
-        .map_err(|e| anyhow::anyhow!("Failed to create rolling file appender: {e}"))?;
+/// Re-resolves `base` and applies the result to the installed logger.
+///
+/// An invalid configuration is returned as an error and leaves the logger
+/// unchanged. The audit trail is kept as is.
+pub(crate) fn logger_reload(base: &LogConfig) -> Result<()> {
+    let log_config = conf::resolve(base)?;
+    log_config_check(&log_config)?;
+
+    let _reloading = RELOADING.lock().unwrap_or_else(|e| e.into_inner());
+    let current = match INSTALLED.lock().unwrap_or_else(|e| e.into_inner()).as_ref() {
+        Some(installed) => installed.config.clone(),
+        None => bail!("Logger is not initialized"),
+    };
 
-        let (non_blocking, guard) = NonBlockingWriter::new(rolling_appender);
-        LOG_GUARD.get_or_init(|| Arc::new(guard));
+    // Opening a file may block, so it is done before taking the logger.
+    let new_sink = sink_settings(&log_config) != sink_settings(&current);
+    let opened = if new_sink {
+        log_dir_create(&log_config)?;
+        file_writer(&log_config)?
+    } else {
+        None
+    };
 
-        let layer = fmt::layer()
-            .with_writer(non_blocking)
-            .with_ansi(log_config.ansi.unwrap_or(false))
-            .with_target(log_config.display_target())
-            .with_level(log_config.display_level())
-            .with_thread_names(log_config.display_thread_name())
-            .with_thread_ids(log_config.display_thread_id());
+    let mut installed = INSTALLED.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(installed) = installed.as_mut() else {
+        bail!("Logger is not initialized");
+    };
+    let writer = match &opened {
+        Some((writer, _)) => Some(writer.clone()),
+        None if new_sink => None,
+        None => installed.writer.clone(),
+    };
 
-        subscriber_init!(env_filter, layer, log_config);
-    } else {
-        let layer = fmt::layer()
-            .with_ansi(log_config.ansi.unwrap_or(true))
-            .with_target(log_config.display_target())
-            .with_level(log_config.display_level())
-            .with_thread_names(log_config.display_thread_name())
-            .with_thread_ids(log_config.display_thread_id());
+    let layer = layers(&log_config, writer.clone(), installed.audit.clone())?;
+    installed.handle.reload(layer)?;
+    if log_config.log_bridge() {
+        tracing_log::log::set_max_level(bridge_level(&log_config));
+    }
 
-        subscriber_init!(env_filter, layer, log_config);
+    if new_sink {
+        if let Some(writer) = &writer {
+            stats::register(writer.clone());
+        }
+        installed.writer = writer;
+        // Dropping the previous guard drains and closes the previous sink.
+        installed.guard = opened.map(|(_, guard)| guard);
     }
+    installed.config = log_config;
     Ok(())
 }
 
@@ -365,6 +938,10 @@ mod tests {
 
         let err_config = LogConfig::default();
         assert!(log_config_check(&err_config).is_err());
+
+        let circular = LogConfig::new("test", "info").with_file_mode(FileMode::Circular);
+        assert!(log_config_check(&circular.clone().with_file("app.log", "4K", 1)).is_err());
+        assert!(log_config_check(&circular.with_file("app.log", "8K", 1)).is_ok());
     }
 
     #[test]
@@ -0,0 +1,328 @@
+use std::fmt::{self, Write as _};
+use tracing::field::{Field, Visit};
+use tracing::{Event, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::field::RecordFields;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::format::UptimeTime;
+use super::LogConfig;
+
+/// Formats events as logfmt: `ts=... level=info target=... msg="..." key=value`.
+///
+/// Event fields follow the message, then the fields of the enclosing spans from
+/// the outermost one inwards. Values are quoted only when they need to be.
+pub(crate) struct Logfmt {
+    timer: Option<Box<dyn FormatTime + Send + Sync>>,
+    display_target: bool,
+    display_level: bool,
+    display_thread_name: bool,
+    display_thread_id: bool,
+}
+
+impl Logfmt {
+    pub(crate) fn new(log_config: &LogConfig) -> Self {
+        let timer: Option<Box<dyn FormatTime + Send + Sync>> = if !log_config.display_time() {
+            None
+        } else if log_config.time_format() == "uptime" {
+            Some(Box::new(UptimeTime))
+        } else {
+            Some(Box::new(LocalTime))
+        };
+
+        Self {
+            timer,
+            display_target: log_config.display_target(),
+            display_level: log_config.display_level(),
+            display_thread_name: log_config.display_thread_name(),
+            display_thread_id: log_config.display_thread_id(),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+        let mut line = Line::default();
+
+        if let Some(timer) = &self.timer {
+            let mut ts = String::new();
+            timer.format_time(&mut Writer::new(&mut ts))?;
+            // Timers made for the text format may pad or bracket their output.
+            line.pair("ts", ts.trim_matches(|c: char| c == '[' || c == ']' || c == ' '));
+        }
+        if self.display_level {
+            line.pair("level", &metadata.level().as_str().to_ascii_lowercase());
+        }
+        if self.display_target {
+            line.pair("target", metadata.target());
+        }
+        if self.display_thread_name {
+            if let Some(name) = std::thread::current().name() {
+                line.pair("thread", name);
+            }
+        }
+        if self.display_thread_id {
+            line.pair("thread_id", &format!("{:?}", std::thread::current().id()));
+        }
+
+        let mut fields = EventFields::default();
+        event.record(&mut fields);
+        line.pair("msg", &fields.message);
+        for (key, value) in &fields.fields {
+            line.pair(key, value);
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let mut names = String::new();
+            let mut span_fields = String::new();
+            for span in scope.from_root() {
+                if !names.is_empty() {
+                    names.push(':');
+                }
+                names.push_str(span.name());
+                if let Some(formatted) = span.extensions().get::<FormattedFields<N>>() {
+                    if !formatted.is_empty() {
+                        span_fields.push(' ');
+                        span_fields.push_str(formatted);
+                    }
+                }
+            }
+            line.pair("span", &names);
+            line.0.push_str(&span_fields);
+        }
+
+        writeln!(writer, "{}", line.0)
+    }
+}
+
+/// Local time with its UTC offset, falling back to UTC when the offset is unknown.
+struct LocalTime;
+
+impl FormatTime for LocalTime {
+    fn format_time(&self, w: &mut Writer<'_>) -> fmt::Result {
+        let now = time::OffsetDateTime::now_local().unwrap_or_else(|_| time::OffsetDateTime::now_utc());
+        let ts = now
+            .format(time::macros::format_description!(
+                "[year]-[month]-[day]T[hour]:[minute]:[second].[subsecond digits:3][offset_hour sign:mandatory]:[offset_minute]"
+            ))
+            .map_err(|_| fmt::Error)?;
+        w.write_str(&ts)
+    }
+}
+
+/// Formats span fields as logfmt pairs, so they can be appended to event lines as is.
+pub(crate) struct LogfmtFields;
+
+impl<'writer> FormatFields<'writer> for LogfmtFields {
+    fn format_fields<R: RecordFields>(&self, mut writer: Writer<'writer>, fields: R) -> fmt::Result {
+        let mut visitor = EventFields::default();
+        fields.record(&mut visitor);
+
+        let mut line = Line::default();
+        if !visitor.message.is_empty() {
+            line.pair("message", &visitor.message);
+        }
+        for (key, value) in &visitor.fields {
+            line.pair(key, value);
+        }
+        writer.write_str(&line.0)
+    }
+}
+
+/// Space separated `key=value` pairs.
+#[derive(Default)]
+struct Line(String);
+
+impl Line {
+    fn pair(&mut self, key: &str, value: &str) {
+        if !self.0.is_empty() {
+            self.0.push(' ');
+        }
+        write_key(&mut self.0, key);
+        self.0.push('=');
+        write_value(&mut self.0, value);
+    }
+}
+
+#[derive(Default)]
+struct EventFields {
+    message: String,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for EventFields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        match field.name() {
+            "message" => self.message = value.to_string(),
+            // Already part of the normalized metadata of bridged `log` records.
+            name if name.starts_with("log.") => {}
+            name => self.fields.push((name, value.to_string())),
+        }
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        match field.name() {
+            "message" => self.message = format!("{value:?}"),
+            name if name.starts_with("log.") => {}
+            name => self.fields.push((name, format!("{value:?}"))),
+        }
+    }
+}
+
+/// Writes `key`, replacing characters that would break the pair with `_`.
+fn write_key(out: &mut String, key: &str) {
+    if key.is_empty() {
+        out.push('_');
+    }
+    out.extend(key.chars().map(|c| {
+        if c == '=' || c == '"' || c.is_whitespace() || c.is_control() {
+            '_'
+        } else {
+            c
+        }
+    }));
+}
+
+/// Writes `value`, quoted and escaped if it is empty or contains spaces, `=`, quotes or control characters.
+pub(crate) fn write_value(out: &mut String, value: &str) {
+    let needs_quotes = value.is_empty()
+        || value
+            .chars()
+            .any(|c| c == '=' || c == '"' || c == '\\' || c.is_whitespace() || c.is_control());
+    if !needs_quotes {
+        out.push_str(value);
+        return;
+    }
+
+    out.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(out, "\\u{{{:04x}}}", c as u32);
+            }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::prelude::*;
+
+    fn value(s: &str) -> String {
+        let mut out = String::new();
+        write_value(&mut out, s);
+        out
+    }
+
+    #[test]
+    fn test_value_quoting() {
+        assert_eq!(value("plain"), "plain");
+        assert_eq!(value("/dev/ttyS0"), "/dev/ttyS0");
+        assert_eq!(value(""), "\"\"");
+        assert_eq!(value("two words"), "\"two words\"");
+        assert_eq!(value("a=b"), "\"a=b\"");
+        assert_eq!(value("say \"hi\""), "\"say \\\"hi\\\"\"");
+        assert_eq!(value("C:\\tmp"), "\"C:\\\\tmp\"");
+        assert_eq!(value("line\nbreak\t\u{1b}"), "\"line\\nbreak\\t\\u{001b}\"");
+
+        let mut key = String::new();
+        write_key(&mut key, "bad key=\"x\"");
+        assert_eq!(key, "bad_key__x_");
+    }
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    #[test]
+    fn test_event_with_spans() {
+        let config = LogConfig::new("test", "info").with_target(true).with_time(false);
+        let buffer = Buffer::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(buffer.clone())
+            .fmt_fields(LogfmtFields)
+            .event_format(Logfmt::new(&config));
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let outer = tracing::info_span!("request", id = 7, peer = "10.0.0.1:80");
+            let _outer = outer.enter();
+            let inner = tracing::info_span!("db", query = "select 1");
+            let _inner = inner.enter();
+            tracing::warn!(target: "app", rows = 3, "query \"slow\"");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "level=warn target=app msg=\"query \\\"slow\\\"\" rows=3 span=request:db id=7 peer=10.0.0.1:80 query=\"select 1\"\n"
+        );
+    }
+
+    #[test]
+    fn test_timestamp_modes() {
+        let config = LogConfig::new("test", "info").with_time_format("uptime");
+        let buffer = Buffer::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(buffer.clone())
+            .fmt_fields(LogfmtFields)
+            .event_format(Logfmt::new(&config));
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!("up");
+        });
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let ts = output.strip_prefix("ts=").unwrap().split(' ').next().unwrap();
+        assert!(ts.parse::<f64>().is_ok(), "Uptime timestamp should be bare seconds: {output}");
+
+        let config = LogConfig::new("test", "info");
+        let buffer = Buffer::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(buffer.clone())
+            .fmt_fields(LogfmtFields)
+            .event_format(Logfmt::new(&config));
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            tracing::info!("local");
+        });
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let ts = output.strip_prefix("ts=").unwrap().split(' ').next().unwrap();
+        assert_eq!(ts.len(), "2026-01-01T00:00:00.000+00:00".len(), "Unexpected timestamp: {output}");
+        assert!(output.ends_with("level=info msg=local\n"));
+    }
+}
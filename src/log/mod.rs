@@ -1,5 +1,46 @@
 #[doc(hidden)]
 mod log_init;
+pub mod audit;
+mod binary;
+mod circular;
+mod conf;
+mod correlation;
+#[cfg(feature = "encryption")]
+mod encryption;
+mod file;
+mod format;
+mod hexdump;
+mod hooks;
+mod logfmt;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod rolling;
+#[cfg(unix)]
+mod shared;
+mod stats;
+mod theme;
+mod writer;
+
+/// Serializes the unit tests that change environment variables.
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
 
 #[doc(inline)]
 pub use log_init::LogConfig;
+pub use binary::{decode_binary, decode_binary_bytes, BinaryEvent};
+pub use circular::read_circular;
+pub use correlation::{request_span, ulid, uuid_v7, TraceParent};
+#[cfg(feature = "encryption")]
+pub use encryption::{decrypt, decrypt_file, generate_keypair, Decrypted, DecryptionKey, EncryptionKey};
+pub use file::{reopen, FileMode};
+pub use format::{HeaderHook, LogFormat};
+pub use hexdump::HexDump;
+pub use hooks::{on_event, LogRecord};
+#[cfg(feature = "otlp")]
+pub use otlp::OtlpConfig;
+pub use rolling::RotationNaming;
+#[cfg(any(test, feature = "testing"))]
+pub(crate) use hooks::RecordVisitor;
+pub use stats::{stats, LogStats};
+pub use theme::{Color, Style, Theme};
+pub use writer::{Durability, FlushPolicy, NonBlockingGuard, NonBlockingWriter, NonBlockingWriterHandle};
@@ -0,0 +1,740 @@
+//! Export of events and spans to an OpenTelemetry collector over OTLP/HTTP.
+//!
+//! Records are encoded as OTLP JSON and posted to `<endpoint>/v1/logs` and
+//! `<endpoint>/v1/traces` by a background thread, in batches. Only plain
+//! `http://` endpoints are supported, which suits a collector or agent running
+//! next to the service.
+
+use crossbeam::channel::{self, Receiver, RecvTimeoutError, Sender, TrySendError};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id, Record};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use super::correlation::{event_context, SpanContext};
+use super::format::Identity;
+use super::LogConfig;
+
+/// Where and how to export over OTLP/HTTP (see [`LogConfig::with_otlp`]).
+///
+/// ```no_run
+/// use alumy::log::OtlpConfig;
+/// use alumy::LogConfig;
+/// use std::time::Duration;
+///
+/// LogConfig::new("my-app", "info")
+///     .with_otlp(OtlpConfig::new("http://localhost:4318").with_batch_timeout(Duration::from_secs(2)))
+///     .init()
+///     .unwrap();
+/// ```
+#[derive(Clone, PartialEq, Eq)]
+pub struct OtlpConfig {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    batch_size: usize,
+    batch_timeout: Duration,
+    max_retries: u32,
+    queue_size: usize,
+}
+
+impl OtlpConfig {
+    /// Exports to the collector at `endpoint`, e.g. `http://localhost:4318`.
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            headers: Vec::new(),
+            batch_size: 512,
+            batch_timeout: Duration::from_secs(1),
+            max_retries: 5,
+            queue_size: 4096,
+        }
+    }
+
+    /// Adds an HTTP header to every export request, e.g. for authentication.
+    ///
+    /// A name that is not an HTTP token, or a value with control characters,
+    /// makes [`LogConfig::init`](super::LogConfig::init) fail.
+    pub fn with_header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Sets how many records are sent together at most (default: 512).
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        self.batch_size = size.max(1);
+        self
+    }
+
+    /// Sets how long a record may wait for its batch to fill (default: 1s).
+    pub fn with_batch_timeout(mut self, timeout: Duration) -> Self {
+        self.batch_timeout = timeout;
+        self
+    }
+
+    /// Sets how many times a failed export is retried, with exponential backoff (default: 5).
+    pub fn with_max_retries(mut self, retries: u32) -> Self {
+        self.max_retries = retries;
+        self
+    }
+
+    /// Sets how many records may wait for export before new ones are dropped (default: 4096).
+    pub fn with_queue_size(mut self, size: usize) -> Self {
+        self.queue_size = size.max(1);
+        self
+    }
+}
+
+/// Leaves out the header values, which often carry credentials.
+impl fmt::Debug for OtlpConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<_> = self.headers.iter().map(|(name, _)| (name, "..")).collect();
+        f.debug_struct("OtlpConfig")
+            .field("endpoint", &self.endpoint)
+            .field("headers", &headers)
+            .field("batch_size", &self.batch_size)
+            .field("batch_timeout", &self.batch_timeout)
+            .field("max_retries", &self.max_retries)
+            .field("queue_size", &self.queue_size)
+            .finish()
+    }
+}
+
+/// A parsed `http://host[:port][/path]` endpoint; an IPv6 host is given in brackets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Endpoint {
+    /// The host name or address, without the brackets of an IPv6 address.
+    host: String,
+    port: u16,
+    path: String,
+}
+
+impl Endpoint {
+    fn parse(url: &str) -> io::Result<Self> {
+        let invalid = |msg: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("{msg}: {url}"));
+
+        let rest = url
+            .strip_prefix("http://")
+            .ok_or_else(|| invalid("OTLP endpoint must be an http:// URL"))?;
+        let (authority, path) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+        let (host, port) = match authority.strip_prefix('[') {
+            Some(bracketed) => {
+                let (host, port) = bracketed
+                    .split_once(']')
+                    .ok_or_else(|| invalid("unterminated OTLP endpoint IPv6 address"))?;
+                let port = match port {
+                    "" => None,
+                    port => Some(port.strip_prefix(':').ok_or_else(|| invalid("invalid OTLP endpoint host"))?),
+                };
+                (host, port)
+            }
+            None => match authority.rsplit_once(':') {
+                Some((host, port)) => (host, Some(port)),
+                None => (authority, None),
+            },
+        };
+        let port = match port {
+            Some(port) => port.parse().map_err(|_| invalid("invalid OTLP endpoint port"))?,
+            None => 4318,
+        };
+        if host.is_empty() {
+            return Err(invalid("missing OTLP endpoint host"));
+        }
+        if host.contains(':') && !authority.starts_with('[') {
+            return Err(invalid("OTLP endpoint IPv6 address must be in brackets"));
+        }
+
+        Ok(Self {
+            host: host.to_string(),
+            port,
+            path: path.trim_end_matches('/').to_string(),
+        })
+    }
+}
+
+/// Rejects a header that would break the request, or smuggle another header into it.
+fn check_header(name: &str, value: &str) -> io::Result<()> {
+    let token = |c: char| c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c);
+    if name.is_empty() || !name.chars().all(token) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid OTLP header name {name:?}")));
+    }
+    if value.chars().any(|c| c.is_control() && c != '\t') {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid OTLP header value for {name}")));
+    }
+    Ok(())
+}
+
+/// How long dropping the [`OtlpGuard`] waits for the queued records to be sent.
+const SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
+enum Message {
+    Log(Value),
+    Span(Value),
+    Shutdown,
+}
+
+/// Keeps the exporter thread alive; dropping it sends what is queued and stops the thread.
+///
+/// The drop waits at most `shutdown_timeout`, so an unreachable collector cannot
+/// hold up the process exit; records not sent by then are lost.
+pub(crate) struct OtlpGuard {
+    sender: Sender<Message>,
+    handle: Option<JoinHandle<()>>,
+    shutdown_timeout: Duration,
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        let deadline = Instant::now() + self.shutdown_timeout;
+        let _ = self.sender.send_deadline(Message::Shutdown, deadline);
+        let Some(handle) = self.handle.take() else {
+            return;
+        };
+        while !handle.is_finished() {
+            if Instant::now() >= deadline {
+                eprintln!("alumy: OTLP export did not finish within {:?}, giving up", self.shutdown_timeout);
+                return;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        let _ = handle.join();
+    }
+}
+
+/// Starts the exporter for `log_config`, returning the layer that feeds it.
+pub(crate) fn spawn(log_config: &LogConfig, config: &OtlpConfig) -> io::Result<(OtlpLayer, OtlpGuard)> {
+    let endpoint = Endpoint::parse(&config.endpoint)?;
+    for (name, value) in &config.headers {
+        check_header(name, value)?;
+    }
+    let (sender, receiver) = channel::bounded(config.queue_size);
+
+    let exporter = Exporter {
+        endpoint,
+        headers: config.headers.clone(),
+        batch_size: config.batch_size,
+        batch_timeout: config.batch_timeout,
+        max_retries: config.max_retries,
+        shutdown_timeout: SHUTDOWN_TIMEOUT,
+        resource: resource(&Identity::new(log_config)),
+    };
+    let handle = thread::Builder::new()
+        .name("alumy-otlp".into())
+        .spawn(move || exporter.run(receiver))?;
+
+    let layer = OtlpLayer {
+        sender: sender.clone(),
+        dropped: AtomicU64::new(0),
+    };
+    Ok((layer, OtlpGuard { sender, handle: Some(handle), shutdown_timeout: SHUTDOWN_TIMEOUT }))
+}
+
+fn resource(identity: &Identity) -> Value {
+    let mut attributes = vec![
+        attribute("service.name", string(&identity.app)),
+        attribute("service.version", string(&identity.version)),
+        attribute("process.pid", json!({ "intValue": identity.pid.to_string() })),
+    ];
+    if let Some(host) = &identity.host {
+        attributes.push(attribute("host.name", string(host)));
+    }
+    json!({ "attributes": attributes })
+}
+
+fn scope() -> Value {
+    json!({ "name": "alumy", "version": crate::version::version() })
+}
+
+struct Exporter {
+    endpoint: Endpoint,
+    headers: Vec<(String, String)>,
+    batch_size: usize,
+    batch_timeout: Duration,
+    max_retries: u32,
+    shutdown_timeout: Duration,
+    resource: Value,
+}
+
+impl Exporter {
+    fn run(self, receiver: Receiver<Message>) {
+        let mut logs = Vec::new();
+        let mut spans = Vec::new();
+        let mut deadline = None;
+        let mut give_up = None;
+
+        loop {
+            let timeout = deadline.map_or(Duration::MAX, |d: Instant| d.saturating_duration_since(Instant::now()));
+            let shutdown = match receiver.recv_timeout(timeout) {
+                Ok(Message::Log(record)) => {
+                    logs.push(record);
+                    false
+                }
+                Ok(Message::Span(span)) => {
+                    spans.push(span);
+                    false
+                }
+                Ok(Message::Shutdown) | Err(RecvTimeoutError::Disconnected) => true,
+                Err(RecvTimeoutError::Timeout) => false,
+            };
+
+            if shutdown {
+                give_up = Some(Instant::now() + self.shutdown_timeout);
+            }
+            let pending = logs.len() + spans.len();
+            if pending > 0 && deadline.is_none() {
+                deadline = Some(Instant::now() + self.batch_timeout);
+            }
+            let due = deadline.is_some_and(|d| Instant::now() >= d);
+            if pending >= self.batch_size || (pending > 0 && (due || shutdown)) {
+                self.export(&mut logs, &mut spans, give_up);
+                deadline = None;
+            }
+            if shutdown {
+                return;
+            }
+        }
+    }
+
+    /// Sends the batches, retrying until `give_up` at the latest.
+    fn export(&self, logs: &mut Vec<Value>, spans: &mut Vec<Value>, give_up: Option<Instant>) {
+        if !logs.is_empty() {
+            let body = json!({
+                "resourceLogs": [{
+                    "resource": self.resource,
+                    "scopeLogs": [{ "scope": scope(), "logRecords": std::mem::take(logs) }],
+                }]
+            });
+            self.send("/v1/logs", &body, give_up);
+        }
+        if !spans.is_empty() {
+            let body = json!({
+                "resourceSpans": [{
+                    "resource": self.resource,
+                    "scopeSpans": [{ "scope": scope(), "spans": std::mem::take(spans) }],
+                }]
+            });
+            self.send("/v1/traces", &body, give_up);
+        }
+    }
+
+    /// Posts `body`, retrying on connection errors and retryable statuses.
+    fn send(&self, path: &str, body: &Value, give_up: Option<Instant>) {
+        let body = body.to_string();
+        let mut backoff = Duration::from_millis(100);
+
+        for attempt in 0..=self.max_retries {
+            let error = match self.post(path, body.as_bytes()) {
+                Ok(status) if (200..300).contains(&status) => return,
+                Ok(status) if matches!(status, 429 | 502 | 503 | 504) => format!("HTTP {status}"),
+                Ok(status) => {
+                    eprintln!("alumy: OTLP collector rejected {path} with HTTP {status}, dropping the batch");
+                    return;
+                }
+                Err(e) => e.to_string(),
+            };
+            let late = give_up.is_some_and(|give_up| Instant::now() + backoff >= give_up);
+            if attempt == self.max_retries || late {
+                eprintln!("alumy: OTLP export to {path} failed after {} attempt(s): {error}", attempt + 1);
+                return;
+            }
+            thread::sleep(backoff);
+            backoff = (backoff * 2).min(Duration::from_secs(5));
+        }
+    }
+
+    /// Sends one HTTP/1.1 request and returns the response status.
+    fn post(&self, path: &str, body: &[u8]) -> io::Result<u16> {
+        let Endpoint { host, port, path: base } = &self.endpoint;
+        let addr = (host.as_str(), *port)
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("cannot resolve {host}")))?;
+        let mut stream = TcpStream::connect_timeout(&addr, Duration::from_secs(5))?;
+        stream.set_read_timeout(Some(Duration::from_secs(10)))?;
+        stream.set_write_timeout(Some(Duration::from_secs(10)))?;
+
+        let authority = if host.contains(':') {
+            format!("[{host}]:{port}")
+        } else {
+            format!("{host}:{port}")
+        };
+        let mut request = format!(
+            "POST {base}{path} HTTP/1.1\r\nHost: {authority}\r\nContent-Type: application/json\r\n\
+             Content-Length: {}\r\nConnection: close\r\n",
+            body.len()
+        );
+        for (name, value) in &self.headers {
+            request.push_str(&format!("{name}: {value}\r\n"));
+        }
+        request.push_str("\r\n");
+        stream.write_all(request.as_bytes())?;
+        stream.write_all(body)?;
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response)?;
+        let status_line = response.split(|&b| b == b'\n').next().unwrap_or_default();
+        std::str::from_utf8(status_line)
+            .ok()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .and_then(|code| code.parse().ok())
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed HTTP response"))
+    }
+}
+
+/// Start time and attributes of a span, kept in its extensions next to its [`SpanContext`].
+struct SpanData {
+    start: SystemTime,
+    attributes: Vec<Value>,
+}
+
+/// Turns events into OTLP log records and closed spans into OTLP spans.
+pub(crate) struct OtlpLayer {
+    sender: Sender<Message>,
+    dropped: AtomicU64,
+}
+
+impl OtlpLayer {
+    fn send(&self, message: Message) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(message) {
+            if self.dropped.fetch_add(1, Ordering::Relaxed) == 0 {
+                eprintln!("alumy: OTLP export queue is full, dropping records");
+            }
+        }
+    }
+}
+
+impl<S> Layer<S> for OtlpLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        attrs.record(&mut fields);
+        span.extensions_mut().insert(SpanData { start: SystemTime::now(), attributes: fields.attributes });
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(id) else { return };
+        let mut fields = Fields::default();
+        values.record(&mut fields);
+        let mut extensions = span.extensions_mut();
+        if let Some(data) = extensions.get_mut::<SpanData>() {
+            data.attributes.extend(fields.attributes);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let (number, text) = severity(metadata.level());
+
+        let mut record = json!({
+            "timeUnixNano": unix_nanos(SystemTime::now()),
+            "severityNumber": number,
+            "severityText": text,
+            "body": string(&fields.message),
+            "attributes": fields.attributes,
+        });
+        let context = event_context(event, &ctx);
+        if let Some(context) = &context {
+            record["traceId"] = json!(format!("{:032x}", context.trace_id));
+            record["spanId"] = json!(format!("{:016x}", context.span_id));
+        }
+        if let Value::Array(attributes) = &mut record["attributes"] {
+            attributes.push(attribute("code.namespace", string(metadata.target())));
+            if let Some(request_id) = context.and_then(|c| c.request_id) {
+                if !attributes.iter().any(|a| a["key"] == "request_id") {
+                    attributes.push(attribute("request_id", string(&request_id)));
+                }
+            }
+        }
+        self.send(Message::Log(record));
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let Some(span) = ctx.span(&id) else { return };
+        let extensions = span.extensions();
+        let (Some(context), Some(data)) = (extensions.get::<SpanContext>(), extensions.get::<SpanData>()) else {
+            return;
+        };
+        if !context.sampled {
+            return;
+        }
+
+        let mut value = json!({
+            "traceId": format!("{:032x}", context.trace_id),
+            "spanId": format!("{:016x}", context.span_id),
+            "name": span.name(),
+            "kind": 1,
+            "startTimeUnixNano": unix_nanos(data.start),
+            "endTimeUnixNano": unix_nanos(SystemTime::now()),
+            "attributes": data.attributes,
+        });
+        if let Some(parent_id) = context.parent_id {
+            value["parentSpanId"] = json!(format!("{parent_id:016x}"));
+        }
+        self.send(Message::Span(value));
+    }
+}
+
+/// Collects the message and the other fields as OTLP attributes.
+#[derive(Default)]
+struct Fields {
+    message: String,
+    attributes: Vec<Value>,
+}
+
+impl Fields {
+    /// Adds an attribute, leaving out the `log.*` metadata of bridged `log` records.
+    fn push(&mut self, field: &Field, value: Value) {
+        if !field.name().starts_with("log.") {
+            self.attributes.push(attribute(field.name(), value));
+        }
+    }
+}
+
+impl Visit for Fields {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        if field.name() == "message" {
+            self.message = value.to_string();
+        } else {
+            self.push(field, string(value));
+        }
+    }
+
+    fn record_i64(&mut self, field: &Field, value: i64) {
+        self.push(field, json!({ "intValue": value.to_string() }));
+    }
+
+    fn record_u64(&mut self, field: &Field, value: u64) {
+        match i64::try_from(value) {
+            Ok(value) => self.record_i64(field, value),
+            Err(_) => self.push(field, string(&value.to_string())),
+        }
+    }
+
+    fn record_f64(&mut self, field: &Field, value: f64) {
+        self.push(field, json!({ "doubleValue": value }));
+    }
+
+    fn record_bool(&mut self, field: &Field, value: bool) {
+        self.push(field, json!({ "boolValue": value }));
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        if field.name() == "message" {
+            self.message = format!("{value:?}");
+        } else {
+            self.push(field, string(&format!("{value:?}")));
+        }
+    }
+}
+
+fn attribute(key: &str, value: Value) -> Value {
+    json!({ "key": key, "value": value })
+}
+
+fn string(value: &str) -> Value {
+    json!({ "stringValue": value })
+}
+
+fn severity(level: &Level) -> (u8, &'static str) {
+    match *level {
+        Level::TRACE => (1, "TRACE"),
+        Level::DEBUG => (5, "DEBUG"),
+        Level::INFO => (9, "INFO"),
+        Level::WARN => (13, "WARN"),
+        Level::ERROR => (17, "ERROR"),
+    }
+}
+
+/// OTLP JSON encodes 64-bit integers as strings.
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::log::correlation::CorrelationLayer;
+    use std::io::BufRead;
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::prelude::*;
+
+    #[test]
+    fn test_config_debug_redacts_headers() {
+        let config = OtlpConfig::new("http://localhost:4318").with_header("Authorization", "Bearer secret");
+        let debug = format!("{config:?}");
+        assert!(debug.contains("Authorization"), "{debug}");
+        assert!(!debug.contains("secret"), "{debug}");
+    }
+
+    #[test]
+    fn test_endpoint_parse() {
+        let endpoint = Endpoint::parse("http://collector:4320/otlp/").unwrap();
+        assert_eq!((endpoint.host.as_str(), endpoint.port, endpoint.path.as_str()), ("collector", 4320, "/otlp"));
+
+        let endpoint = Endpoint::parse("http://localhost").unwrap();
+        assert_eq!((endpoint.port, endpoint.path.as_str()), (4318, ""));
+
+        assert!(Endpoint::parse("https://collector:4318").is_err());
+        assert!(Endpoint::parse("http://:4318").is_err());
+        assert!(Endpoint::parse("http://collector:port").is_err());
+
+        let endpoint = Endpoint::parse("http://[::1]:4320/otlp").unwrap();
+        assert_eq!((endpoint.host.as_str(), endpoint.port, endpoint.path.as_str()), ("::1", 4320, "/otlp"));
+        let endpoint = Endpoint::parse("http://[fd00::1]").unwrap();
+        assert_eq!((endpoint.host.as_str(), endpoint.port), ("fd00::1", 4318));
+        assert!(Endpoint::parse("http://::1:4318").is_err());
+        assert!(Endpoint::parse("http://[::1:4318").is_err());
+        assert!(Endpoint::parse("http://[::1]4318").is_err());
+    }
+
+    #[test]
+    fn test_header_check() {
+        assert!(check_header("Authorization", "Bearer a\tb").is_ok());
+        assert!(check_header("X-Scope-OrgID", "tenant-1").is_ok());
+        assert!(check_header("", "value").is_err());
+        assert!(check_header("Bad Name", "value").is_err());
+        assert!(check_header("X-Evil:", "value").is_err());
+        assert!(check_header("X-Token", "a\r\nInjected: yes").is_err());
+        assert!(check_header("X-Token", "a\0b").is_err());
+
+        let config = OtlpConfig::new("http://localhost:4318").with_header("X-Token", "a\nb");
+        assert!(spawn(&LogConfig::new("test", "info"), &config).is_err());
+    }
+
+    /// The path and body of each accepted request.
+    type Requests = Arc<Mutex<Vec<(String, Value)>>>;
+
+    /// A stand-in collector that answers `statuses` in turn, then 200, and keeps the requests.
+    fn collector(statuses: Vec<u16>) -> (String, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Requests::default();
+
+        let received = requests.clone();
+        thread::spawn(move || {
+            let mut statuses = statuses.into_iter();
+            for stream in listener.incoming() {
+                let mut reader = io::BufReader::new(stream.unwrap());
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let path = line.split_whitespace().nth(1).unwrap().to_string();
+
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                        length = value.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+
+                let status = statuses.next().unwrap_or(200);
+                if status == 200 {
+                    received.lock().unwrap().push((path, serde_json::from_slice(&body).unwrap()));
+                }
+                let response = format!("HTTP/1.1 {status} X\r\nContent-Length: 0\r\nConnection: close\r\n\r\n");
+                reader.get_mut().write_all(response.as_bytes()).unwrap();
+            }
+        });
+        (endpoint, requests)
+    }
+
+    #[test]
+    fn test_export_with_retry() {
+        let (endpoint, requests) = collector(vec![503]);
+        let log_config = LogConfig::new("otlp-test", "info").with_version("9.9.9");
+        let config = OtlpConfig::new(endpoint).with_batch_size(100).with_batch_timeout(Duration::from_millis(50));
+        let (layer, guard) = spawn(&log_config, &config).unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(CorrelationLayer).with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            let span = tracing::info_span!("request", request_id = "req-7");
+            let _span = span.enter();
+            tracing::warn!(target: "app", retries = 3, "slow response");
+        });
+        drop(guard);
+
+        let requests = requests.lock().unwrap();
+        let logs = &requests.iter().find(|(path, _)| path == "/v1/logs").unwrap().1;
+        let resource = &logs["resourceLogs"][0]["resource"]["attributes"];
+        assert_eq!(resource[0], attribute("service.name", string("otlp-test")));
+        assert_eq!(resource[1], attribute("service.version", string("9.9.9")));
+
+        let record = &logs["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        assert_eq!(record["severityNumber"], 13);
+        assert_eq!(record["body"], string("slow response"));
+        assert_eq!(record["attributes"][0], attribute("retries", json!({ "intValue": "3" })));
+        assert_eq!(record["attributes"][2], attribute("request_id", string("req-7")));
+
+        let traces = &requests.iter().find(|(path, _)| path == "/v1/traces").unwrap().1;
+        let span = &traces["resourceSpans"][0]["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["name"], "request");
+        assert_eq!(span["attributes"][0], attribute("request_id", string("req-7")));
+        assert_eq!(record["traceId"], span["traceId"]);
+        assert_eq!(record["spanId"], span["spanId"]);
+    }
+
+    #[test]
+    fn test_bridged_log_fields_are_skipped() {
+        let (endpoint, requests) = collector(vec![]);
+        let log_config = LogConfig::new("otlp-test", "info");
+        let config = OtlpConfig::new(endpoint).with_batch_timeout(Duration::from_millis(50));
+        let (layer, guard) = spawn(&log_config, &config).unwrap();
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || {
+            tracing::info!(log.target = "dep", log.module_path = "dep::io", log.line = 7u64, bytes = 12u64, "bridged");
+        });
+        drop(guard);
+
+        let requests = requests.lock().unwrap();
+        let record = &requests[0].1["resourceLogs"][0]["scopeLogs"][0]["logRecords"][0];
+        let attributes = record["attributes"].as_array().unwrap();
+        assert_eq!(attributes[0], attribute("bytes", json!({ "intValue": "12" })));
+        assert!(attributes.iter().all(|a| !a["key"].as_str().unwrap().starts_with("log.")), "{attributes:?}");
+    }
+
+    #[test]
+    fn test_shutdown_gives_up_after_timeout() {
+        // A collector that accepts connections but never answers.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let endpoint = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let streams: Vec<_> = listener.incoming().collect();
+            drop(streams);
+        });
+
+        let log_config = LogConfig::new("otlp-test", "info");
+        let (layer, mut guard) = spawn(&log_config, &OtlpConfig::new(endpoint)).unwrap();
+        guard.shutdown_timeout = Duration::from_millis(200);
+
+        let subscriber = tracing_subscriber::registry().with(layer);
+        tracing::subscriber::with_default(subscriber, || tracing::info!("never acknowledged"));
+
+        let start = Instant::now();
+        drop(guard);
+        assert!(start.elapsed() < Duration::from_secs(2), "Shutdown took {:?}", start.elapsed());
+    }
+}
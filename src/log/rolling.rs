@@ -0,0 +1,607 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+#[cfg(feature = "encryption")]
+use super::encryption::{EncryptionKey, Encryptor};
+use super::stats::Stats;
+use super::writer::Sink;
+
+/// Produces the preamble written at the top of every newly opened log file.
+pub(crate) type HeaderFn = Arc<dyn Fn() -> Vec<u8> + Send + Sync>;
+
+/// How rotated log files are named.
+///
+/// The active file keeps its configured name in every scheme; `max_files` is
+/// the number of rotated files kept next to it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RotationNaming {
+    /// `app.log.1`, `app.log.2`, ..., renumbered at every rotation (default).
+    #[default]
+    Numeric,
+    /// `app.2026-10-17T13-00-00Z.log`, the UTC time of the rotation.
+    Timestamp,
+    /// `app-1.log`, `app-2.log`, ..., numbered in rotation order, continuing after restarts.
+    Sequence,
+}
+
+/// Settings for a [`RollingFile`], kept separately so the file can be reopened.
+#[derive(Clone)]
+pub(crate) struct RollingOptions {
+    pub(crate) path: PathBuf,
+    pub(crate) max_size: u64,
+    pub(crate) max_files: usize,
+    pub(crate) naming: RotationNaming,
+    pub(crate) header: Option<HeaderFn>,
+    #[cfg(feature = "encryption")]
+    pub(crate) encryption: Option<EncryptionKey>,
+}
+
+/// Size-based rolling log file.
+///
+/// Old files are named according to [`RotationNaming`]; by default they use the
+/// Debian-style scheme `path`, `path.1`, ..., `path.N` where `N` is `max_files`.
+/// The named schemes never rename a file once it has been rotated, so log
+/// collectors can track files by name. A header produced by [`RollingOptions::header`] is
+/// written at the top of every new file, including after each rotation; a file
+/// appended to after a restart is not given another one. With
+/// encryption, each opening also starts a new encrypted stream, ended when the
+/// file is rotated or closed.
+pub(crate) struct RollingFile {
+    options: RollingOptions,
+    writer: Output,
+    size: u64,
+    stats: Arc<Stats>,
+}
+
+/// The active file, sealing every write as a chunk of its encrypted stream if enabled.
+struct Output<W = BufWriter<File>> {
+    file: W,
+    #[cfg(feature = "encryption")]
+    encryption: Option<Encryption>,
+}
+
+/// The encrypted stream of the active file, `None` once ended or after a failed
+/// write, until the next write starts a new one.
+#[cfg(feature = "encryption")]
+struct Encryption {
+    key: EncryptionKey,
+    stream: Option<Encryptor>,
+}
+
+impl<W: Write> Output<W> {
+    /// Writes `buf`, returning the number of bytes it took in the file.
+    fn write(&mut self, buf: &[u8]) -> io::Result<u64> {
+        #[cfg(feature = "encryption")]
+        if let Some(encryption) = &mut self.encryption {
+            let mut sealed = Vec::with_capacity(buf.len() + 32);
+            let stream = match &mut encryption.stream {
+                Some(stream) => stream,
+                None => {
+                    let (stream, header) = Encryptor::start(&encryption.key)?;
+                    sealed = header;
+                    encryption.stream.insert(stream)
+                }
+            };
+            // A failed write may leave part of a chunk in the file and has used
+            // up its nonces, so the stream cannot go on after it: the next
+            // write starts a new one, which readers resync on.
+            let result = stream.seal(buf, &mut sealed).and_then(|()| self.file.write_all(&sealed));
+            if result.is_err() {
+                encryption.stream = None;
+            }
+            return result.map(|()| sealed.len() as u64);
+        }
+        self.file.write_all(buf)?;
+        Ok(buf.len() as u64)
+    }
+
+    /// Ends the encrypted stream, if any, and flushes the file.
+    fn finish(&mut self) -> io::Result<()> {
+        #[cfg(feature = "encryption")]
+        if let Some(stream) = self.encryption.as_mut().and_then(|encryption| encryption.stream.take()) {
+            let mut last = Vec::new();
+            stream.finish(&mut last)?;
+            self.file.write_all(&last)?;
+        }
+        self.file.flush()
+    }
+}
+
+impl RollingFile {
+    pub(crate) fn open(options: RollingOptions, stats: Arc<Stats>) -> io::Result<Self> {
+        let (writer, size) = open_file(&options)?;
+        Ok(Self {
+            options,
+            writer,
+            size,
+            stats,
+        })
+    }
+
+    fn filename_for(&self, n: usize) -> PathBuf {
+        let mut name = self.options.path.clone().into_os_string();
+        if n > 0 {
+            name.push(format!(".{n}"));
+        }
+        name.into()
+    }
+
+    fn rotate_files(&self) -> io::Result<()> {
+        match self.options.naming {
+            RotationNaming::Numeric => self.shift_numbered(),
+            RotationNaming::Timestamp | RotationNaming::Sequence => self.rename_active(),
+        }
+    }
+
+    /// Shifts `path.N` to `path.N+1`, dropping the oldest file.
+    fn shift_numbered(&self) -> io::Result<()> {
+        let max_files = self.options.max_files.max(1);
+        let _ = fs::remove_file(self.filename_for(max_files));
+        let mut result = Ok(());
+        for i in (0..max_files).rev() {
+            if let Err(e) = fs::rename(self.filename_for(i), self.filename_for(i + 1)) {
+                // Keep going so as many files as possible are shifted.
+                if e.kind() != io::ErrorKind::NotFound {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Moves the active file to a new name of the configured scheme and drops
+    /// the oldest rotated files beyond `max_files`.
+    fn rename_active(&self) -> io::Result<()> {
+        let names = Names::new(&self.options.path);
+        let rotated = names.rotated(self.options.naming)?;
+        let next = rotated.last().map_or(1, |(key, _)| key.1 + 1);
+        let mut rotated: Vec<_> = rotated.into_iter().map(|(_, path)| path).collect();
+
+        let target = match self.options.naming {
+            RotationNaming::Sequence => {
+                names.dir.join(format!("{}-{next}{}", names.stem, names.ext))
+            }
+            _ => {
+                let ts = time::OffsetDateTime::now_utc()
+                    .format(time::macros::format_description!(
+                        "[year]-[month]-[day]T[hour]-[minute]-[second]Z"
+                    ))
+                    .map_err(io::Error::other)?;
+                (0..)
+                    .map(|n| match n {
+                        0 => names.dir.join(format!("{}.{ts}{}", names.stem, names.ext)),
+                        n => names.dir.join(format!("{}.{ts}-{n}{}", names.stem, names.ext)),
+                    })
+                    .find(|path| !path.exists())
+                    .unwrap_or_default()
+            }
+        };
+
+        match fs::rename(&self.options.path, &target) {
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            result => result?,
+        }
+        rotated.push(target);
+
+        let excess = rotated.len().saturating_sub(self.options.max_files.max(1));
+        let mut result = Ok(());
+        for path in &rotated[..excess] {
+            if let Err(e) = fs::remove_file(path) {
+                if e.kind() != io::ErrorKind::NotFound {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    fn rollover(&mut self) -> io::Result<()> {
+        self.writer.finish()?;
+        if let Err(e) = self.rotate_files() {
+            eprintln!("alumy: failed to rotate log file {}: {e}", self.options.path.display());
+        }
+        (self.writer, self.size) = open_file(&self.options)?;
+        Stats::inc(&self.stats.rotations);
+        Ok(())
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size >= self.options.max_size {
+            self.rollover()?;
+        }
+        self.size += self.writer.write(buf)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.file.flush()
+    }
+}
+
+impl Drop for RollingFile {
+    fn drop(&mut self) {
+        let _ = self.writer.finish();
+    }
+}
+
+impl Sink for RollingFile {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.writer.file.flush()?;
+        self.writer.file.get_ref().sync_data()
+    }
+}
+
+/// The parts of the active file name that rotated names are built from.
+struct Names {
+    dir: PathBuf,
+    stem: String,
+    ext: String,
+}
+
+/// Orders rotated files oldest first: rotation time, then sequence or
+/// same-second counter, then name. All of them come from the name, which,
+/// unlike the modification time, copying or touching a file leaves alone.
+type RotatedKey = (u64, u64, String);
+
+impl Names {
+    fn new(path: &Path) -> Self {
+        Self {
+            dir: path
+                .parent()
+                .filter(|p| !p.as_os_str().is_empty())
+                .unwrap_or_else(|| Path::new("."))
+                .to_path_buf(),
+            stem: path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default(),
+            ext: path
+                .extension()
+                .map(|e| format!(".{}", e.to_string_lossy()))
+                .unwrap_or_default(),
+        }
+    }
+
+    /// Lists the rotated files of `naming`, oldest first.
+    fn rotated(&self, naming: RotationNaming) -> io::Result<Vec<(RotatedKey, PathBuf)>> {
+        let mut rotated = Vec::new();
+        for entry in fs::read_dir(&self.dir)? {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            let Some(middle) = name.strip_suffix(&self.ext).and_then(|n| n.strip_prefix(&self.stem)) else {
+                continue;
+            };
+
+            let key = match naming {
+                RotationNaming::Sequence => match middle.strip_prefix('-').map(str::parse::<u64>) {
+                    Some(Ok(seq)) => (0, seq, name),
+                    _ => continue,
+                },
+                _ => match middle.strip_prefix('.').and_then(parse_timestamp) {
+                    Some((ts, n)) => (ts, n, name),
+                    None => continue,
+                },
+            };
+            rotated.push((key, entry.path()));
+        }
+        rotated.sort();
+        Ok(rotated)
+    }
+}
+
+/// Lists the rotated files of the active file `path` in any naming scheme, oldest first.
+pub(crate) fn rotated_files(path: &Path) -> io::Result<Vec<PathBuf>> {
+    let names = Names::new(path);
+    let mut files = Vec::new();
+
+    let prefix = format!("{}.", path.file_name().unwrap_or_default().to_string_lossy());
+    let mut numbered = Vec::new();
+    for entry in fs::read_dir(&names.dir)? {
+        let entry = entry?;
+        let name = entry.file_name().to_string_lossy().into_owned();
+        if let Some(Ok(n)) = name.strip_prefix(&prefix).map(str::parse::<u64>) {
+            numbered.push((n, entry.path()));
+        }
+    }
+    numbered.sort_by_key(|(n, _)| std::cmp::Reverse(*n));
+    files.extend(numbered.into_iter().map(|(_, path)| path));
+
+    for naming in [RotationNaming::Sequence, RotationNaming::Timestamp] {
+        files.extend(names.rotated(naming)?.into_iter().map(|(_, path)| path));
+    }
+    Ok(files)
+}
+
+/// Parses `2026-10-17T13-00-00Z` with an optional `-N` counter, returning the
+/// timestamp's digits as a number, `20261017130000`, and the counter.
+fn parse_timestamp(s: &str) -> Option<(u64, u64)> {
+    const PATTERN: &[u8] = b"dddd-dd-ddTdd-dd-ddZ";
+
+    let ts = s.get(..PATTERN.len())?;
+    let counter = &s[PATTERN.len()..];
+    let valid = ts.bytes().zip(PATTERN).all(|(c, &p)| match p {
+        b'd' => c.is_ascii_digit(),
+        p => c == p,
+    });
+    let counter = match counter.strip_prefix('-') {
+        _ if !valid => return None,
+        None if counter.is_empty() => 0,
+        Some(n) => n.parse().ok()?,
+        None => return None,
+    };
+    let ts = ts.bytes().filter(u8::is_ascii_digit).fold(0, |ts, d| ts * 10 + u64::from(d - b'0'));
+    Some((ts, counter))
+}
+
+/// Opens the active file for appending, starts the encrypted stream and writes the header, if any,
+/// to a new file.
+fn open_file(options: &RollingOptions) -> io::Result<(Output, u64)> {
+    let file = OpenOptions::new().append(true).create(true).open(&options.path)?;
+    let mut size = file.metadata()?.len();
+    let new_file = size == 0;
+    let mut writer = Output {
+        file: BufWriter::new(file),
+        #[cfg(feature = "encryption")]
+        encryption: None,
+    };
+
+    #[cfg(feature = "encryption")]
+    if let Some(key) = &options.encryption {
+        let (stream, header) = Encryptor::start(key)?;
+        writer.file.write_all(&header)?;
+        size += header.len() as u64;
+        writer.encryption = Some(Encryption {
+            key: key.clone(),
+            stream: Some(stream),
+        });
+    }
+
+    if let Some(header) = options.header.as_ref().filter(|_| new_file) {
+        size += writer.write(&header())?;
+    }
+
+    Ok((writer, size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("alumy-rolling-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let dir = temp_dir("rotate");
+        let options = RollingOptions {
+            path: dir.join("app.log"),
+            max_size: 100,
+            max_files: 2,
+            naming: RotationNaming::Numeric,
+            header: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        let stats = Arc::new(Stats::default());
+        let mut file = RollingFile::open(options, stats.clone()).unwrap();
+        for i in 0..20 {
+            file.write_all(format!("line {i:02} with some padding text\n").as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+
+        let mut names: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["app.log", "app.log.1", "app.log.2"]);
+        assert!(stats.snapshot(0).rotations > 2);
+        assert!(fs::read_to_string(dir.join("app.log")).unwrap().contains("line 19"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_header_on_open_and_rotation() {
+        let dir = temp_dir("header");
+        let options = RollingOptions {
+            path: dir.join("app.log"),
+            max_size: 64,
+            max_files: 3,
+            naming: RotationNaming::Numeric,
+            header: Some(Arc::new(|| b"# header\n".to_vec())),
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        let mut file = RollingFile::open(options.clone(), Arc::default()).unwrap();
+        for _ in 0..4 {
+            file.write_all(b"0123456789012345678901234567890123456789\n").unwrap();
+        }
+        file.flush().unwrap();
+
+        for name in ["app.log", "app.log.1"] {
+            let content = fs::read_to_string(dir.join(name)).unwrap();
+            assert!(content.starts_with("# header\n"), "{name} lacks header");
+        }
+
+        // Appending after a restart continues the file without another header.
+        drop(file);
+        let before = fs::read_to_string(dir.join("app.log")).unwrap();
+        let options = RollingOptions { max_size: 1024, ..options };
+        let mut file = RollingFile::open(options, Arc::default()).unwrap();
+        file.write_all(b"after restart\n").unwrap();
+        file.flush().unwrap();
+        let after = fs::read_to_string(dir.join("app.log")).unwrap();
+        assert_eq!(after, format!("{before}after restart\n"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    fn rotated_names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<_> = fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .filter(|name| name != "app.log")
+            .collect();
+        names.sort();
+        names
+    }
+
+    fn write_lines(options: RollingOptions, count: usize) {
+        let mut file = RollingFile::open(options, Arc::default()).unwrap();
+        for i in 0..count {
+            file.write_all(format!("line {i:02} with some padding text\n").as_bytes()).unwrap();
+        }
+        file.flush().unwrap();
+    }
+
+    #[test]
+    fn test_sequence_naming() {
+        let dir = temp_dir("sequence");
+        let options = RollingOptions {
+            path: dir.join("app.log"),
+            max_size: 100,
+            max_files: 3,
+            naming: RotationNaming::Sequence,
+            header: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        write_lines(options.clone(), 20);
+        assert_eq!(rotated_names(&dir), ["app-2.log", "app-3.log", "app-4.log"]);
+        let newest = fs::read_to_string(dir.join("app-4.log")).unwrap();
+
+        // Numbering continues after a restart and rotated files are never renamed.
+        write_lines(options, 4);
+        assert_eq!(rotated_names(&dir), ["app-3.log", "app-4.log", "app-5.log"]);
+        assert_eq!(fs::read_to_string(dir.join("app-4.log")).unwrap(), newest);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_timestamp_naming() {
+        let dir = temp_dir("timestamp");
+        fs::write(dir.join("app.notes.log"), "not a rotated file").unwrap();
+        let options = RollingOptions {
+            path: dir.join("app.log"),
+            max_size: 100,
+            max_files: 2,
+            naming: RotationNaming::Timestamp,
+            header: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        write_lines(options, 20);
+
+        let names = rotated_names(&dir);
+        assert_eq!(names.len(), 3, "{names:?}");
+        assert!(names.contains(&"app.notes.log".to_string()));
+        let rotated: Vec<_> = names.iter().filter(|n| n.as_str() != "app.notes.log").collect();
+        for name in &rotated {
+            let middle = name.strip_prefix("app.").unwrap().strip_suffix(".log").unwrap();
+            assert!(parse_timestamp(middle).is_some(), "Unexpected name {name}");
+        }
+        // The kept files are the newest: together with the active file they end with line 19.
+        let active = fs::read_to_string(dir.join("app.log")).unwrap();
+        assert!(active.contains("line 19"));
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_retention_follows_names_not_mtime() {
+        let dir = temp_dir("retention");
+        let older = dir.join("app.2020-01-01T00-00-00Z.log");
+        let newer = dir.join("app.2021-01-01T00-00-00Z.log");
+        fs::write(&newer, "2021\n").unwrap();
+        fs::write(&older, "2020\n").unwrap();
+        // The older file was touched last, e.g. by a copy.
+        let now = std::time::SystemTime::now();
+        File::options().write(true).open(&newer).unwrap().set_modified(now - std::time::Duration::from_secs(60)).unwrap();
+        File::options().write(true).open(&older).unwrap().set_modified(now).unwrap();
+
+        let options = RollingOptions {
+            path: dir.join("app.log"),
+            max_size: 100,
+            max_files: 2,
+            naming: RotationNaming::Timestamp,
+            header: None,
+            #[cfg(feature = "encryption")]
+            encryption: None,
+        };
+        write_lines(options, 5);
+
+        assert!(!older.exists(), "The oldest file by name should be removed");
+        assert!(newer.exists());
+        assert_eq!(rotated_names(&dir).len(), 2);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("2026-10-17T13-00-00Z"), Some((20261017130000, 0)));
+        assert_eq!(parse_timestamp("2026-10-17T13-00-00Z-12"), Some((20261017130000, 12)));
+        assert_eq!(parse_timestamp("2026-10-17T13-00"), None);
+        assert_eq!(parse_timestamp("2026-10-17T13-00-00"), None);
+        assert_eq!(parse_timestamp("2026-10-17T13-00-00Zx"), None);
+        assert_eq!(parse_timestamp("notes"), None);
+    }
+
+    /// Takes half of the write it is told to fail, then fails it.
+    #[cfg(feature = "encryption")]
+    struct Flaky {
+        data: Vec<u8>,
+        fail: bool,
+    }
+
+    #[cfg(feature = "encryption")]
+    impl Write for Flaky {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if std::mem::take(&mut self.fail) {
+                self.data.extend_from_slice(&buf[..buf.len() / 2]);
+                return Err(io::Error::other("disk full"));
+            }
+            self.data.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[cfg(feature = "encryption")]
+    #[test]
+    fn test_failed_write_starts_new_stream() {
+        use crate::log::{decrypt, DecryptionKey};
+
+        let key = [3u8; 32];
+        let (stream, header) = Encryptor::start(&EncryptionKey::Symmetric(key)).unwrap();
+        let mut output = Output {
+            file: Flaky { data: header, fail: false },
+            encryption: Some(Encryption {
+                key: EncryptionKey::Symmetric(key),
+                stream: Some(stream),
+            }),
+        };
+
+        output.write(b"one\n").unwrap();
+        output.file.fail = true;
+        assert!(output.write(b"two\n").is_err());
+        output.write(b"three\n").unwrap();
+        output.write(b"four\n").unwrap();
+
+        output.finish().unwrap();
+
+        let plain = decrypt(&output.file.data, &DecryptionKey::Symmetric(key)).unwrap();
+        assert_eq!(plain.data, b"one\nthree\nfour\n");
+        assert_eq!(plain.unfinished, 1);
+    }
+}
@@ -0,0 +1,245 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use super::stats::Stats;
+use super::writer::Sink;
+
+/// Buffered records are written out once they exceed this size, even without a flush.
+const MAX_BUFFERED: usize = 64 * 1024;
+
+/// Rolling log file that can be shared by several processes.
+///
+/// Records are buffered and appended with a single `O_APPEND` write, so lines
+/// from different processes never interleave. Rotation is serialized through an
+/// exclusive `flock` on `<path>.lock`; every process checks, under that lock,
+/// whether another one has already rotated the file and reopens it if so.
+pub(crate) struct SharedFile {
+    path: PathBuf,
+    lock: File,
+    file: File,
+    identity: (u64, u64),
+    max_size: u64,
+    max_files: usize,
+    buffer: Vec<u8>,
+    stats: Arc<Stats>,
+}
+
+impl SharedFile {
+    pub(crate) fn open(path: impl AsRef<Path>, max_size: u64, max_files: usize) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let mut lock_path = path.clone().into_os_string();
+        lock_path.push(".lock");
+
+        let lock = OpenOptions::new().write(true).create(true).truncate(false).open(lock_path)?;
+        let (file, identity) = open_append(&path)?;
+
+        Ok(Self {
+            path,
+            lock,
+            file,
+            identity,
+            max_size,
+            max_files: max_files.max(1),
+            buffer: Vec::with_capacity(MAX_BUFFERED),
+            stats: Arc::default(),
+        })
+    }
+
+    /// Counts rotations performed by this process in `stats`.
+    pub(crate) fn with_stats(mut self, stats: Arc<Stats>) -> Self {
+        self.stats = stats;
+        self
+    }
+
+    fn filename_for(&self, n: usize) -> PathBuf {
+        let mut name = self.path.clone().into_os_string();
+        if n > 0 {
+            name.push(format!(".{n}"));
+        }
+        name.into()
+    }
+
+    /// Shifts `path.N` to `path.N+1`, dropping the oldest file. Must hold the lock.
+    fn rotate(&mut self) -> io::Result<()> {
+        let _ = fs::remove_file(self.filename_for(self.max_files));
+        for i in (0..self.max_files).rev() {
+            match fs::rename(self.filename_for(i), self.filename_for(i + 1)) {
+                Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+        Stats::inc(&self.stats.rotations);
+        Ok(())
+    }
+
+    /// Appends the buffered records, which are dropped whether or not that
+    /// succeeds: a failed write may have put part of them in the file already,
+    /// and writing them again would duplicate it. The caller keeps what it has
+    /// not flushed.
+    fn write_records(&mut self) -> io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+        let result = self.append_buffer();
+        self.buffer.clear();
+        result
+    }
+
+    fn append_buffer(&mut self) -> io::Result<()> {
+        let _lock = FileLock::exclusive(&self.lock)?;
+
+        let size = match fs::metadata(&self.path) {
+            Ok(metadata) if file_identity(&metadata) == self.identity => metadata.len(),
+            // Rotated or removed by another process.
+            _ => {
+                (self.file, self.identity) = open_append(&self.path)?;
+                self.file.metadata()?.len()
+            }
+        };
+
+        if size > 0 && size + self.buffer.len() as u64 > self.max_size {
+            self.rotate()?;
+            (self.file, self.identity) = open_append(&self.path)?;
+        }
+
+        self.file.write_all(&self.buffer)
+    }
+}
+
+impl Write for SharedFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        if self.buffer.len() >= MAX_BUFFERED {
+            self.write_records()?;
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.write_records()
+    }
+}
+
+impl Sink for SharedFile {
+    fn sync_data(&mut self) -> io::Result<()> {
+        self.write_records()?;
+        self.file.sync_data()
+    }
+}
+
+impl Drop for SharedFile {
+    fn drop(&mut self) {
+        let _ = self.write_records();
+    }
+}
+
+/// Holds an exclusive `flock` until dropped.
+struct FileLock(RawFd);
+
+impl FileLock {
+    fn exclusive(file: &File) -> io::Result<Self> {
+        let fd = file.as_raw_fd();
+        loop {
+            if unsafe { libc::flock(fd, libc::LOCK_EX) } == 0 {
+                return Ok(Self(fd));
+            }
+            let e = io::Error::last_os_error();
+            if e.kind() != io::ErrorKind::Interrupted {
+                return Err(e);
+            }
+        }
+    }
+}
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        unsafe { libc::flock(self.0, libc::LOCK_UN) };
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<(File, (u64, u64))> {
+    let file = OpenOptions::new().append(true).create(true).open(path)?;
+    let identity = file_identity(&file.metadata()?);
+    Ok((file, identity))
+}
+
+fn file_identity(metadata: &fs::Metadata) -> (u64, u64) {
+    (metadata.dev(), metadata.ino())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn test_concurrent_writers_share_rotation() {
+        let dir = std::env::temp_dir().join(format!("alumy-shared-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+
+        // Each writer has its own descriptors, just like separate processes.
+        let writers: Vec<_> = (0..4)
+            .map(|w| {
+                let path = path.clone();
+                thread::spawn(move || {
+                    let mut file = SharedFile::open(&path, 4096, 100).unwrap();
+                    for i in 0..200 {
+                        file.write_all(format!("writer {w} line {i:03}\n").as_bytes()).unwrap();
+                        if i % 10 == 0 {
+                            file.flush().unwrap();
+                        }
+                    }
+                })
+            })
+            .collect();
+        for w in writers {
+            w.join().unwrap();
+        }
+
+        let mut lines = Vec::new();
+        for entry in fs::read_dir(&dir).unwrap() {
+            let entry = entry.unwrap();
+            if entry.file_name().to_string_lossy().ends_with(".lock") {
+                continue;
+            }
+            let content = fs::read_to_string(entry.path()).unwrap();
+            assert!(content.len() as u64 <= 4096 + 64 * 1024);
+            lines.extend(content.lines().map(str::to_string));
+        }
+
+        assert_eq!(lines.len(), 800);
+        assert!(lines.iter().all(|l| l.starts_with("writer ") && l.len() == "writer 0 line 000".len()));
+        assert!(fs::read_dir(&dir).unwrap().count() > 2, "Should have rotated");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_failed_write_drops_records() {
+        let dir = std::env::temp_dir().join(format!("alumy-shared-failed-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("app.log");
+
+        let mut file = SharedFile::open(&path, 4096, 2).unwrap();
+        file.write_all(b"one\n").unwrap();
+        file.flush().unwrap();
+
+        file.write_all(b"two\n").unwrap();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(file.flush().is_err());
+
+        fs::create_dir_all(&dir).unwrap();
+        file.write_all(b"three\n").unwrap();
+        file.flush().unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap(), "three\n");
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
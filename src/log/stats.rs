@@ -0,0 +1,116 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use super::writer::NonBlockingWriter;
+
+/// The writer created by `logger_init` or the last reload, kept so [`stats`] can read its counters.
+static LOG_WRITER: Mutex<Option<NonBlockingWriter>> = Mutex::new(None);
+
+/// Snapshot of the logging pipeline counters.
+///
+/// All counters are cumulative since the writer was created.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LogStats {
+    /// Events waiting in the queue for the writer thread.
+    pub queue_depth: usize,
+    /// Events handed to the sink by the writer thread.
+    pub events_written: u64,
+    /// Bytes handed to the sink by the writer thread.
+    pub bytes_written: u64,
+    /// Failed writes or flushes reported by the sink.
+    pub write_errors: u64,
+    /// Events discarded because the writer had already shut down.
+    pub dropped_events: u64,
+    /// Log file rotations, including external rotations picked up in [`FileMode::Reopen`](super::FileMode::Reopen).
+    pub rotations: u64,
+    /// Time the most recent event spent between being logged and being written.
+    pub last_latency: Duration,
+    /// Largest latency observed so far.
+    pub max_latency: Duration,
+    /// Records the [`on_event`](super::on_event) hooks fell too far behind to
+    /// receive, process-wide; only reported by [`stats`].
+    pub hook_dropped: u64,
+}
+
+/// Counters shared between the writer handles, the writer thread and the sinks.
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+    pub(crate) events_written: AtomicU64,
+    pub(crate) bytes_written: AtomicU64,
+    pub(crate) write_errors: AtomicU64,
+    pub(crate) dropped_events: AtomicU64,
+    pub(crate) rotations: AtomicU64,
+    last_latency_us: AtomicU64,
+    max_latency_us: AtomicU64,
+}
+
+impl Stats {
+    pub(crate) fn inc(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_write(&self, bytes: usize, latency: Duration) {
+        let latency_us = latency.as_micros().min(u64::MAX as u128) as u64;
+        self.events_written.fetch_add(1, Ordering::Relaxed);
+        self.bytes_written.fetch_add(bytes as u64, Ordering::Relaxed);
+        self.last_latency_us.store(latency_us, Ordering::Relaxed);
+        self.max_latency_us.fetch_max(latency_us, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self, queue_depth: usize) -> LogStats {
+        LogStats {
+            queue_depth,
+            events_written: self.events_written.load(Ordering::Relaxed),
+            bytes_written: self.bytes_written.load(Ordering::Relaxed),
+            write_errors: self.write_errors.load(Ordering::Relaxed),
+            dropped_events: self.dropped_events.load(Ordering::Relaxed),
+            rotations: self.rotations.load(Ordering::Relaxed),
+            last_latency: Duration::from_micros(self.last_latency_us.load(Ordering::Relaxed)),
+            max_latency: Duration::from_micros(self.max_latency_us.load(Ordering::Relaxed)),
+            hook_dropped: 0,
+        }
+    }
+}
+
+pub(crate) fn register(writer: NonBlockingWriter) {
+    *LOG_WRITER.lock().unwrap_or_else(|e| e.into_inner()) = Some(writer);
+}
+
+/// Returns a snapshot of the counters of the file logger installed by [`LogConfig::init`](super::LogConfig::init).
+///
+/// All counters but [`hook_dropped`](LogStats::hook_dropped) are zero when
+/// logging only to the console.
+pub fn stats() -> LogStats {
+    let stats = LOG_WRITER
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(NonBlockingWriter::stats)
+        .unwrap_or_default();
+    LogStats {
+        hook_dropped: super::hooks::dropped(),
+        ..stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_record_write() {
+        let stats = Stats::default();
+        stats.record_write(10, Duration::from_micros(300));
+        stats.record_write(20, Duration::from_micros(100));
+        Stats::inc(&stats.rotations);
+
+        let snapshot = stats.snapshot(3);
+        assert_eq!(snapshot.queue_depth, 3);
+        assert_eq!(snapshot.events_written, 2);
+        assert_eq!(snapshot.bytes_written, 30);
+        assert_eq!(snapshot.rotations, 1);
+        assert_eq!(snapshot.last_latency, Duration::from_micros(100));
+        assert_eq!(snapshot.max_latency, Duration::from_micros(300));
+    }
+}
@@ -0,0 +1,382 @@
+use std::fmt;
+use std::io::IsTerminal;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use tracing::{Event, Level, Subscriber};
+use tracing_log::NormalizeEvent;
+use tracing_subscriber::fmt::format::Writer;
+use tracing_subscriber::fmt::time::FormatTime;
+use tracing_subscriber::fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields};
+use tracing_subscriber::registry::LookupSpan;
+
+use super::format::UptimeTime;
+use super::LogConfig;
+
+/// A terminal color.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Color {
+    Black,
+    Red,
+    Green,
+    Yellow,
+    Blue,
+    Magenta,
+    Cyan,
+    White,
+    /// One of the 256 colors of the extended palette; 8-15 are the bright variants of the above.
+    Fixed(u8),
+    /// A 24-bit color, for terminals that support it.
+    Rgb(u8, u8, u8),
+}
+
+/// The color and emphasis of one part of a console record.
+///
+/// ```
+/// use alumy::log::{Color, Style};
+///
+/// let style = Style::new().fg(Color::Red).bold();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Style {
+    fg: Option<Color>,
+    bold: bool,
+    dimmed: bool,
+}
+
+impl Style {
+    /// A style that leaves the text unchanged.
+    pub const fn new() -> Self {
+        Self { fg: None, bold: false, dimmed: false }
+    }
+
+    pub const fn fg(mut self, color: Color) -> Self {
+        self.fg = Some(color);
+        self
+    }
+
+    pub const fn bold(mut self) -> Self {
+        self.bold = true;
+        self
+    }
+
+    pub const fn dimmed(mut self) -> Self {
+        self.dimmed = true;
+        self
+    }
+
+    /// Writes `text` in this style.
+    fn paint(&self, w: &mut Writer<'_>, text: impl fmt::Display) -> fmt::Result {
+        let mut codes = Vec::new();
+        if self.bold {
+            codes.push("1".to_string());
+        }
+        if self.dimmed {
+            codes.push("2".to_string());
+        }
+        codes.extend(self.fg.map(|color| match color {
+            Color::Black => "30".to_string(),
+            Color::Red => "31".to_string(),
+            Color::Green => "32".to_string(),
+            Color::Yellow => "33".to_string(),
+            Color::Blue => "34".to_string(),
+            Color::Magenta => "35".to_string(),
+            Color::Cyan => "36".to_string(),
+            Color::White => "37".to_string(),
+            Color::Fixed(n) => format!("38;5;{n}"),
+            Color::Rgb(r, g, b) => format!("38;2;{r};{g};{b}"),
+        }));
+
+        if codes.is_empty() {
+            write!(w, "{text}")
+        } else {
+            write!(w, "\x1b[{}m{text}\x1b[0m", codes.join(";"))
+        }
+    }
+}
+
+impl From<Color> for Style {
+    fn from(color: Color) -> Self {
+        Self::new().fg(color)
+    }
+}
+
+/// Styles of the console output when ANSI colors are enabled.
+///
+/// The default matches the palette of `tracing-subscriber`.
+///
+/// ```
+/// use alumy::log::{Color, Style, Theme};
+/// use alumy::{Level, LogConfig};
+///
+/// let theme = Theme::default()
+///     .with_level(Level::INFO, Color::Cyan)
+///     .with_level(Level::ERROR, Style::new().fg(Color::Red).bold())
+///     .with_target(Color::Fixed(244));
+/// let config = LogConfig::new("my-app", "info").with_theme(theme);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Theme {
+    pub trace: Style,
+    pub debug: Style,
+    pub info: Style,
+    pub warn: Style,
+    pub error: Style,
+    pub target: Style,
+    pub time: Style,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            trace: Color::Magenta.into(),
+            debug: Color::Blue.into(),
+            info: Color::Green.into(),
+            warn: Color::Yellow.into(),
+            error: Color::Red.into(),
+            target: Style::new().dimmed(),
+            time: Style::new().dimmed(),
+        }
+    }
+}
+
+impl Theme {
+    /// Sets the style of the level name of `level` records.
+    pub fn with_level(mut self, level: Level, style: impl Into<Style>) -> Self {
+        *self.level_mut(level) = style.into();
+        self
+    }
+
+    /// Sets the style of the target.
+    pub fn with_target(mut self, style: impl Into<Style>) -> Self {
+        self.target = style.into();
+        self
+    }
+
+    /// Sets the style of the timestamp.
+    pub fn with_time(mut self, style: impl Into<Style>) -> Self {
+        self.time = style.into();
+        self
+    }
+
+    pub fn level(&self, level: Level) -> Style {
+        match level {
+            Level::TRACE => self.trace,
+            Level::DEBUG => self.debug,
+            Level::INFO => self.info,
+            Level::WARN => self.warn,
+            Level::ERROR => self.error,
+        }
+    }
+
+    fn level_mut(&mut self, level: Level) -> &mut Style {
+        match level {
+            Level::TRACE => &mut self.trace,
+            Level::DEBUG => &mut self.debug,
+            Level::INFO => &mut self.info,
+            Level::WARN => &mut self.warn,
+            Level::ERROR => &mut self.error,
+        }
+    }
+}
+
+/// Decides whether the console output is colored.
+///
+/// A non-empty `NO_COLOR` always disables colors and `CLICOLOR_FORCE` other
+/// than `0` enables them. Otherwise `configured` applies, and without it
+/// colors are used only when stdout is a terminal.
+pub(crate) fn console_ansi(configured: Option<bool>) -> bool {
+    let var = |key: &str| std::env::var(key).ok().filter(|v| !v.is_empty());
+    if var("NO_COLOR").is_some() {
+        false
+    } else if var("CLICOLOR_FORCE").is_some_and(|v| v != "0") {
+        true
+    } else {
+        configured.unwrap_or_else(|| std::io::stdout().is_terminal())
+    }
+}
+
+/// Formats colored text records with a [`Theme`], in the layout of the plain text format.
+pub(crate) struct Themed {
+    theme: Theme,
+    timer: Option<Box<dyn FormatTime + Send + Sync>>,
+    display_target: bool,
+    display_level: bool,
+    display_thread_name: bool,
+    display_thread_id: bool,
+}
+
+impl Themed {
+    pub(crate) fn new(log_config: &LogConfig) -> Self {
+        let timer: Option<Box<dyn FormatTime + Send + Sync>> = if !log_config.display_time() {
+            None
+        } else if log_config.time_format() == "uptime" {
+            Some(Box::new(UptimeTime))
+        } else {
+            Some(Box::new(tracing_subscriber::fmt::time::LocalTime::new(time::macros::format_description!(
+                "[year]-[month]-[day] [hour]:[minute]:[second].[subsecond digits:3]"
+            ))))
+        };
+
+        Self {
+            theme: log_config.theme.unwrap_or_default(),
+            timer,
+            display_target: log_config.display_target(),
+            display_level: log_config.display_level(),
+            display_thread_name: log_config.display_thread_name(),
+            display_thread_id: log_config.display_thread_id(),
+        }
+    }
+}
+
+impl<S, N> FormatEvent<S, N> for Themed
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    N: for<'a> FormatFields<'a> + 'static,
+{
+    fn format_event(&self, ctx: &FmtContext<'_, S, N>, mut writer: Writer<'_>, event: &Event<'_>) -> fmt::Result {
+        let normalized = event.normalized_metadata();
+        let metadata = normalized.as_ref().unwrap_or_else(|| event.metadata());
+
+        if let Some(timer) = &self.timer {
+            let mut ts = String::new();
+            timer.format_time(&mut Writer::new(&mut ts))?;
+            self.theme.time.paint(&mut writer, ts)?;
+            writer.write_char(' ')?;
+        }
+        if self.display_level {
+            let level = *metadata.level();
+            self.theme.level(level).paint(&mut writer, format_args!("{level:>5}"))?;
+            writer.write_char(' ')?;
+        }
+
+        let thread = std::thread::current();
+        if self.display_thread_name {
+            match thread.name() {
+                Some(name) => {
+                    // Right-align to the longest name seen so far, as the plain text format does.
+                    static WIDTH: AtomicUsize = AtomicUsize::new(0);
+                    let width = WIDTH.fetch_max(name.len(), Ordering::Relaxed).max(name.len());
+                    write!(writer, "{name:>width$} ")?;
+                }
+                None if !self.display_thread_id => write!(writer, "{:0>2?} ", thread.id())?,
+                None => {}
+            }
+        }
+        if self.display_thread_id {
+            write!(writer, "{:0>2?} ", thread.id())?;
+        }
+
+        if let Some(scope) = ctx.event_scope() {
+            let bold = Style::new().bold();
+            let mut seen = false;
+            for span in scope.from_root() {
+                bold.paint(&mut writer, span.name())?;
+                if let Some(fields) = span.extensions().get::<FormattedFields<N>>() {
+                    if !fields.is_empty() {
+                        bold.paint(&mut writer, "{")?;
+                        write!(writer, "{fields}")?;
+                        bold.paint(&mut writer, "}")?;
+                    }
+                }
+                Style::new().dimmed().paint(&mut writer, ":")?;
+                seen = true;
+            }
+            if seen {
+                writer.write_char(' ')?;
+            }
+        }
+
+        if self.display_target {
+            self.theme.target.paint(&mut writer, format_args!("{}:", metadata.target()))?;
+            writer.write_char(' ')?;
+        }
+
+        ctx.format_fields(writer.by_ref(), event)?;
+        writeln!(writer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::fmt::MakeWriter;
+    use tracing_subscriber::prelude::*;
+
+    #[derive(Clone, Default)]
+    struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+    impl io::Write for Buffer {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl<'a> MakeWriter<'a> for Buffer {
+        type Writer = Buffer;
+
+        fn make_writer(&'a self) -> Self::Writer {
+            self.clone()
+        }
+    }
+
+    fn painted(style: Style, text: &str) -> String {
+        let mut out = String::new();
+        style.paint(&mut Writer::new(&mut out), text).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_style_codes() {
+        assert_eq!(painted(Style::new(), "x"), "x");
+        assert_eq!(painted(Color::Red.into(), "x"), "\x1b[31mx\x1b[0m");
+        assert_eq!(painted(Style::new().fg(Color::Fixed(208)).bold(), "x"), "\x1b[1;38;5;208mx\x1b[0m");
+        assert_eq!(painted(Style::new().dimmed().fg(Color::Rgb(1, 2, 3)), "x"), "\x1b[2;38;2;1;2;3mx\x1b[0m");
+    }
+
+    #[test]
+    fn test_themed_event() {
+        let theme = Theme::default()
+            .with_level(Level::WARN, Style::new().fg(Color::Magenta).bold())
+            .with_target(Color::Cyan);
+        let config = LogConfig::new("test", "info").with_target(true).with_time(false).with_theme(theme);
+        let buffer = Buffer::default();
+        let layer = tracing_subscriber::fmt::layer()
+            .with_writer(buffer.clone())
+            .with_ansi(false)
+            .event_format(Themed::new(&config));
+
+        tracing::subscriber::with_default(tracing_subscriber::registry().with(layer), || {
+            let span = tracing::info_span!("request", id = 7);
+            let _span = span.enter();
+            tracing::warn!(target: "app", rows = 3, "slow query");
+        });
+
+        let output = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            output,
+            "\x1b[1;35m WARN\x1b[0m \x1b[1mrequest\x1b[0m\x1b[1m{\x1b[0mid=7\x1b[1m}\x1b[0m\x1b[2m:\x1b[0m \
+             \x1b[36mapp:\x1b[0m slow query rows=3\n"
+        );
+    }
+
+    #[test]
+    fn test_console_ansi_env() {
+        // Both variables are process-wide, so every case is checked in this one test.
+        let _env = crate::log::ENV_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        std::env::set_var("CLICOLOR_FORCE", "1");
+        assert!(console_ansi(Some(false)));
+        std::env::set_var("NO_COLOR", "1");
+        assert!(!console_ansi(Some(true)));
+        std::env::remove_var("NO_COLOR");
+        std::env::set_var("CLICOLOR_FORCE", "0");
+        assert!(console_ansi(Some(true)));
+        assert!(!console_ansi(Some(false)));
+        std::env::remove_var("CLICOLOR_FORCE");
+    }
+}
@@ -0,0 +1,645 @@
+use crossbeam::channel::{Receiver, RecvTimeoutError, Sender};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+
+use super::stats::{LogStats, Stats};
+
+/// Number of spare event buffers kept for reuse by the writer handles.
+const POOL_SIZE: usize = 256;
+/// Capacity of a freshly allocated event buffer.
+const INITIAL_CAPACITY: usize = 256;
+/// Buffers that grew beyond this size are freed instead of recycled.
+const MAX_RECYCLED_CAPACITY: usize = 64 * 1024;
+/// How long the writer thread waits at shutdown for handles that are still sending.
+const SENDING_GRACE: Duration = Duration::from_millis(100);
+
+/// Controls when the writer thread flushes the underlying sink.
+///
+/// Queued events are always written back to back into the sink, so a buffered
+/// sink (such as the rolling file appender) turns a burst of events into a few
+/// large writes. The policy only decides when the buffered data is pushed out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FlushPolicy {
+    /// Flush once the queue has been drained (default).
+    #[default]
+    Drained,
+    /// Flush after every single event.
+    EveryEvent,
+    /// Flush when the given time has elapsed since the last flush.
+    Interval(Duration),
+    /// Flush once at least the given number of bytes has been written since the last flush.
+    Bytes(usize),
+    /// Flush immediately after an `ERROR` event; other events are flushed when
+    /// the sink's buffer fills, or once `max_delay` has elapsed since the last flush.
+    OnError { max_delay: Duration },
+}
+
+/// Controls when the writer thread forces written data to storage with `fdatasync`.
+///
+/// Flushing only hands data to the operating system; after a power loss the last
+/// lines may still be missing unless they were synced.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Durability {
+    /// Never sync explicitly; the operating system writes data back on its own (default).
+    #[default]
+    None,
+    /// Sync pending data once the given time has elapsed since the last sync.
+    Interval(Duration),
+    /// Sync right after an event at the given level or more severe, e.g. `Level::WARN`.
+    AtLevel(Level),
+    /// Sync after every event.
+    EveryWrite,
+}
+
+/// A log destination the writer thread can flush and force to storage.
+pub(crate) trait Sink: Write {
+    /// Forces written data to storage. Sinks that are not backed by a file do nothing.
+    fn sync_data(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<S: Sink + ?Sized> Sink for Box<S> {
+    fn sync_data(&mut self) -> io::Result<()> {
+        (**self).sync_data()
+    }
+}
+
+/// Adapts an arbitrary writer that cannot be synced.
+struct Unsynced<W>(W);
+
+impl<W: Write> Write for Unsynced<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.0.flush()
+    }
+}
+
+impl<W: Write> Sink for Unsynced<W> {}
+
+enum Message {
+    Event { buf: Vec<u8>, level: Level, enqueued: Instant },
+    Shutdown,
+}
+
+struct WriterLoop<S: Sink> {
+    writer: S,
+    policy: FlushPolicy,
+    durability: Durability,
+    recycle: Sender<Vec<u8>>,
+    stats: Arc<Stats>,
+    sending: Arc<AtomicUsize>,
+    pending: usize,
+    last_flush: Instant,
+    dirty: bool,
+    last_sync: Instant,
+}
+
+impl<S: Sink> WriterLoop<S> {
+    fn run(mut self, receiver: Receiver<Message>) {
+        loop {
+            let msg = match self.deadline() {
+                Some(deadline) => match receiver.recv_deadline(deadline) {
+                    Ok(msg) => msg,
+                    Err(RecvTimeoutError::Timeout) => {
+                        self.on_timeout();
+                        continue;
+                    }
+                    Err(RecvTimeoutError::Disconnected) => break,
+                },
+                None => match receiver.recv() {
+                    Ok(msg) => msg,
+                    Err(_) => break,
+                },
+            };
+
+            match msg {
+                Message::Event { buf, level, enqueued } => {
+                    self.write(buf, enqueued);
+                    if self.should_sync(level) {
+                        self.sync();
+                    } else if self.should_flush(level, &receiver) {
+                        self.flush();
+                    }
+                }
+                Message::Shutdown => break,
+            }
+        }
+
+        // Handles that saw the writer open are let finish sending, unless one is
+        // stalled; later ones drop their events.
+        let deadline = Instant::now() + SENDING_GRACE;
+        while self.sending.load(Ordering::SeqCst) > 0 && Instant::now() < deadline {
+            thread::yield_now();
+        }
+        for msg in receiver.try_iter() {
+            if let Message::Event { buf, enqueued, .. } = msg {
+                self.write(buf, enqueued);
+            }
+        }
+        if self.durability != Durability::None && self.dirty {
+            self.sync();
+        } else {
+            self.flush();
+        }
+    }
+
+    /// Returns when the next timed flush or sync is due, if one is pending.
+    fn deadline(&self) -> Option<Instant> {
+        let flush = match self.policy {
+            FlushPolicy::Interval(interval) | FlushPolicy::OnError { max_delay: interval } if self.pending > 0 => {
+                Some(self.last_flush + interval)
+            }
+            _ => None,
+        };
+        let sync = match self.durability {
+            Durability::Interval(interval) if self.dirty => Some(self.last_sync + interval),
+            _ => None,
+        };
+        match (flush, sync) {
+            (Some(flush), Some(sync)) => Some(flush.min(sync)),
+            (flush, sync) => flush.or(sync),
+        }
+    }
+
+    fn on_timeout(&mut self) {
+        if let Durability::Interval(interval) = self.durability {
+            if self.dirty && self.last_sync.elapsed() >= interval {
+                self.sync();
+            }
+        }
+        if let FlushPolicy::Interval(interval) | FlushPolicy::OnError { max_delay: interval } = self.policy {
+            if self.pending > 0 && self.last_flush.elapsed() >= interval {
+                self.flush();
+            }
+        }
+    }
+
+    fn write(&mut self, mut buf: Vec<u8>, enqueued: Instant) {
+        if self.writer.write_all(&buf).is_err() {
+            Stats::inc(&self.stats.write_errors);
+        }
+        self.pending += buf.len();
+        self.dirty = true;
+        self.stats.record_write(buf.len(), enqueued.elapsed());
+
+        if buf.capacity() <= MAX_RECYCLED_CAPACITY {
+            buf.clear();
+            let _ = self.recycle.try_send(buf);
+        }
+    }
+
+    fn should_flush(&self, level: Level, receiver: &Receiver<Message>) -> bool {
+        match self.policy {
+            FlushPolicy::Drained => receiver.is_empty(),
+            FlushPolicy::EveryEvent => true,
+            FlushPolicy::Interval(interval) => self.last_flush.elapsed() >= interval,
+            FlushPolicy::Bytes(bytes) => self.pending >= bytes,
+            FlushPolicy::OnError { max_delay } => level <= Level::ERROR || self.last_flush.elapsed() >= max_delay,
+        }
+    }
+
+    fn should_sync(&self, level: Level) -> bool {
+        match self.durability {
+            Durability::None => false,
+            Durability::Interval(interval) => self.last_sync.elapsed() >= interval,
+            Durability::AtLevel(threshold) => level <= threshold,
+            Durability::EveryWrite => true,
+        }
+    }
+
+    fn flush(&mut self) {
+        if self.writer.flush().is_err() {
+            Stats::inc(&self.stats.write_errors);
+        }
+        self.pending = 0;
+        self.last_flush = Instant::now();
+    }
+
+    fn sync(&mut self) {
+        self.flush();
+        if self.writer.sync_data().is_err() {
+            Stats::inc(&self.stats.write_errors);
+        }
+        self.dirty = false;
+        self.last_sync = Instant::now();
+    }
+}
+
+pub struct NonBlockingWriter {
+    shared: Arc<Shared>,
+}
+
+impl NonBlockingWriter {
+    /// Spawns the writer thread with the default [`FlushPolicy`].
+    pub fn new<W: Write + Send + 'static>(writer: W) -> (Self, NonBlockingGuard) {
+        Self::with_flush_policy(writer, FlushPolicy::default())
+    }
+
+    /// Spawns the writer thread, flushing `writer` according to `policy`.
+    pub fn with_flush_policy<W: Write + Send + 'static>(writer: W, policy: FlushPolicy) -> (Self, NonBlockingGuard) {
+        Self::spawn(Unsynced(writer), policy, Durability::None, Arc::default())
+    }
+
+    /// Spawns the writer thread for `sink`, recording its counters into `stats`.
+    ///
+    /// Sinks that report their own errors or rotations share the same `stats`.
+    pub(crate) fn spawn<S: Sink + Send + 'static>(
+        writer: S,
+        policy: FlushPolicy,
+        durability: Durability,
+        stats: Arc<Stats>,
+    ) -> (Self, NonBlockingGuard) {
+        let (sender, receiver) = crossbeam::channel::unbounded::<Message>();
+        let (recycle, pool) = crossbeam::channel::bounded::<Vec<u8>>(POOL_SIZE);
+        let sending = Arc::new(AtomicUsize::new(0));
+        let shared = Arc::new(Shared {
+            sender,
+            pool,
+            closed: AtomicBool::new(false),
+            sending: sending.clone(),
+            stats: stats.clone(),
+        });
+
+        let writer_loop = WriterLoop {
+            writer,
+            policy,
+            durability,
+            recycle,
+            stats,
+            sending,
+            pending: 0,
+            last_flush: Instant::now(),
+            dirty: false,
+            last_sync: Instant::now(),
+        };
+
+        let handle = thread::Builder::new()
+            .name("tracing-writer".to_string())
+            .stack_size(2 * 1024 * 1024)
+            .spawn(move || writer_loop.run(receiver))
+            .expect("Failed to spawn logging thread");
+
+        let guard = NonBlockingGuard {
+            shared: shared.clone(),
+            handle: Some(handle),
+        };
+
+        (NonBlockingWriter { shared }, guard)
+    }
+
+    /// Returns a snapshot of this writer's counters.
+    pub fn stats(&self) -> LogStats {
+        self.shared.stats.snapshot(self.shared.sender.len())
+    }
+}
+
+impl Clone for NonBlockingWriter {
+    fn clone(&self) -> Self {
+        Self {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// State shared between the writer handles, the guard and the writer thread.
+///
+/// Sending needs no lock: the channel itself is lock-free and shutdown is
+/// signalled through `closed`. `sending` counts the handles between checking
+/// `closed` and sending, which the writer thread waits for before its final drain.
+struct Shared {
+    sender: Sender<Message>,
+    pool: Receiver<Vec<u8>>,
+    closed: AtomicBool,
+    sending: Arc<AtomicUsize>,
+    stats: Arc<Stats>,
+}
+
+impl Shared {
+    fn buffer(&self) -> Vec<u8> {
+        self.pool.try_recv().unwrap_or_else(|_| Vec::with_capacity(INITIAL_CAPACITY))
+    }
+}
+
+pub struct NonBlockingWriterHandle {
+    shared: Arc<Shared>,
+    buffer: Vec<u8>,
+    level: Level,
+}
+
+impl Write for NonBlockingWriterHandle {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buffer.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        if self.buffer.is_empty() {
+            return Ok(());
+        }
+
+        self.shared.sending.fetch_add(1, Ordering::SeqCst);
+        let sent = !self.shared.closed.load(Ordering::SeqCst)
+            && self
+                .shared
+                .sender
+                .send(Message::Event {
+                    buf: std::mem::take(&mut self.buffer),
+                    level: self.level,
+                    enqueued: Instant::now(),
+                })
+                .is_ok();
+        self.shared.sending.fetch_sub(1, Ordering::SeqCst);
+        if !sent {
+            self.buffer.clear();
+            Stats::inc(&self.shared.stats.dropped_events);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for NonBlockingWriterHandle {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl<'a> MakeWriter<'a> for NonBlockingWriter {
+    type Writer = NonBlockingWriterHandle;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        NonBlockingWriterHandle {
+            shared: self.shared.clone(),
+            buffer: self.shared.buffer(),
+            level: Level::TRACE,
+        }
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        NonBlockingWriterHandle {
+            shared: self.shared.clone(),
+            buffer: self.shared.buffer(),
+            level: *meta.level(),
+        }
+    }
+}
+
+pub struct NonBlockingGuard {
+    shared: Arc<Shared>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl Drop for NonBlockingGuard {
+    fn drop(&mut self) {
+        if !self.shared.closed.swap(true, Ordering::SeqCst) {
+            let _ = self.shared.sender.send(Message::Shutdown);
+        }
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::Mutex;
+
+    #[derive(Clone, Default)]
+    struct Recorder {
+        data: Arc<Mutex<Vec<u8>>>,
+        flushes: Arc<Mutex<usize>>,
+        syncs: Arc<Mutex<usize>>,
+    }
+
+    impl Sink for Recorder {
+        fn sync_data(&mut self) -> io::Result<()> {
+            *self.syncs.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    impl Write for Recorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.data.lock().unwrap().extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            *self.flushes.lock().unwrap() += 1;
+            Ok(())
+        }
+    }
+
+    fn send(writer: &NonBlockingWriter, level: Level, msg: &str) {
+        let mut handle = writer.make_writer();
+        handle.level = level;
+        handle.write_all(msg.as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn test_every_event_flushes_each_message() {
+        let recorder = Recorder::default();
+        let (writer, guard) = NonBlockingWriter::with_flush_policy(recorder.clone(), FlushPolicy::EveryEvent);
+        for i in 0..10 {
+            send(&writer, Level::INFO, &format!("line {i}\n"));
+        }
+        drop(guard);
+
+        let data = String::from_utf8(recorder.data.lock().unwrap().clone()).unwrap();
+        assert_eq!(data.lines().count(), 10);
+        assert!(*recorder.flushes.lock().unwrap() >= 10);
+    }
+
+    #[test]
+    fn test_bytes_policy_coalesces_flushes() {
+        let recorder = Recorder::default();
+        let (writer, guard) = NonBlockingWriter::with_flush_policy(recorder.clone(), FlushPolicy::Bytes(1024));
+        for _ in 0..100 {
+            send(&writer, Level::INFO, "0123456789\n");
+        }
+        drop(guard);
+
+        assert_eq!(recorder.data.lock().unwrap().len(), 1100);
+        assert!(*recorder.flushes.lock().unwrap() <= 2);
+    }
+
+    #[test]
+    fn test_on_error_flushes_immediately() {
+        let recorder = Recorder::default();
+        let policy = FlushPolicy::OnError {
+            max_delay: Duration::from_millis(200),
+        };
+        let (writer, guard) = NonBlockingWriter::with_flush_policy(recorder.clone(), policy);
+        send(&writer, Level::INFO, "info\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*recorder.flushes.lock().unwrap(), 0);
+
+        send(&writer, Level::ERROR, "error\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*recorder.flushes.lock().unwrap(), 1);
+
+        // Without errors, the delay still bounds how long an event stays unflushed.
+        send(&writer, Level::INFO, "info\n");
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(*recorder.flushes.lock().unwrap(), 2);
+        drop(guard);
+    }
+
+    #[test]
+    fn test_interval_flushes_when_idle() {
+        let recorder = Recorder::default();
+        let (writer, _guard) =
+            NonBlockingWriter::with_flush_policy(recorder.clone(), FlushPolicy::Interval(Duration::from_millis(20)));
+        send(&writer, Level::INFO, "info\n");
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*recorder.flushes.lock().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_buffers_are_recycled() {
+        let recorder = Recorder::default();
+        let (writer, _guard) = NonBlockingWriter::new(recorder.clone());
+        send(&writer, Level::INFO, "first\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(writer.shared.pool.len(), 1);
+
+        let handle = writer.make_writer();
+        assert!(handle.buffer.is_empty());
+        assert!(handle.buffer.capacity() >= INITIAL_CAPACITY);
+        assert!(writer.shared.pool.is_empty());
+    }
+
+    #[test]
+    fn test_writes_after_shutdown_are_ignored() {
+        let recorder = Recorder::default();
+        let (writer, guard) = NonBlockingWriter::new(recorder.clone());
+        send(&writer, Level::INFO, "before\n");
+        drop(guard);
+        send(&writer, Level::INFO, "after\n");
+
+        assert_eq!(recorder.data.lock().unwrap().as_slice(), b"before\n");
+        assert_eq!(writer.stats().dropped_events, 1);
+    }
+
+    #[test]
+    fn test_events_racing_shutdown_are_written_or_counted() {
+        for _ in 0..20 {
+            let recorder = Recorder::default();
+            let (writer, guard) = NonBlockingWriter::new(recorder.clone());
+            let threads: Vec<_> = (0..4)
+                .map(|_| {
+                    let writer = writer.clone();
+                    thread::spawn(move || {
+                        for _ in 0..500 {
+                            send(&writer, Level::INFO, "line\n");
+                        }
+                    })
+                })
+                .collect();
+            drop(guard);
+            for t in threads {
+                t.join().unwrap();
+            }
+
+            let written = recorder.data.lock().unwrap().len() as u64 / 5;
+            assert_eq!(written + writer.stats().dropped_events, 4 * 500);
+        }
+    }
+
+    #[test]
+    fn test_shutdown_does_not_wait_for_stalled_sender() {
+        let recorder = Recorder::default();
+        let (writer, guard) = NonBlockingWriter::new(recorder.clone());
+        send(&writer, Level::INFO, "line\n");
+        writer.shared.sending.fetch_add(1, Ordering::SeqCst);
+
+        let start = Instant::now();
+        drop(guard);
+        assert!(start.elapsed() < Duration::from_secs(1));
+        assert_eq!(recorder.data.lock().unwrap().as_slice(), b"line\n");
+    }
+
+    #[test]
+    fn test_concurrent_writers() {
+        let recorder = Recorder::default();
+        let (writer, guard) = NonBlockingWriter::new(recorder.clone());
+        let threads: Vec<_> = (0..8)
+            .map(|_| {
+                let writer = writer.clone();
+                thread::spawn(move || {
+                    for _ in 0..1000 {
+                        send(&writer, Level::INFO, "line\n");
+                    }
+                })
+            })
+            .collect();
+        for t in threads {
+            t.join().unwrap();
+        }
+        drop(guard);
+
+        assert_eq!(recorder.data.lock().unwrap().len(), 8 * 1000 * 5);
+
+        let stats = writer.stats();
+        assert_eq!(stats.queue_depth, 0);
+        assert_eq!(stats.events_written, 8 * 1000);
+        assert_eq!(stats.bytes_written, 8 * 1000 * 5);
+    }
+
+    #[test]
+    fn test_durability_at_level() {
+        let recorder = Recorder::default();
+        let (writer, guard) =
+            NonBlockingWriter::spawn(recorder.clone(), FlushPolicy::default(), Durability::AtLevel(Level::WARN), Arc::default());
+        send(&writer, Level::INFO, "info\n");
+        send(&writer, Level::DEBUG, "debug\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*recorder.syncs.lock().unwrap(), 0);
+
+        send(&writer, Level::WARN, "warn\n");
+        send(&writer, Level::ERROR, "error\n");
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*recorder.syncs.lock().unwrap(), 2);
+        drop(guard);
+    }
+
+    #[test]
+    fn test_durability_every_write() {
+        let recorder = Recorder::default();
+        let (writer, guard) =
+            NonBlockingWriter::spawn(recorder.clone(), FlushPolicy::default(), Durability::EveryWrite, Arc::default());
+        for _ in 0..5 {
+            send(&writer, Level::INFO, "info\n");
+        }
+        drop(guard);
+        assert_eq!(*recorder.syncs.lock().unwrap(), 5);
+        assert!(*recorder.flushes.lock().unwrap() >= 5);
+    }
+
+    #[test]
+    fn test_durability_interval_syncs_when_idle() {
+        let recorder = Recorder::default();
+        let (writer, _guard) = NonBlockingWriter::spawn(
+            recorder.clone(),
+            FlushPolicy::default(),
+            Durability::Interval(Duration::from_millis(20)),
+            Arc::default(),
+        );
+        send(&writer, Level::INFO, "info\n");
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(*recorder.syncs.lock().unwrap(), 1);
+
+        thread::sleep(Duration::from_millis(50));
+        assert_eq!(*recorder.syncs.lock().unwrap(), 1, "Nothing new to sync");
+    }
+}
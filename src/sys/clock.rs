@@ -0,0 +1,234 @@
+//! Detection of wall-clock jumps, such as NTP or RTC corrections.
+//!
+//! The offset between [`SystemTime::now`] and the time since boot only changes
+//! when the wall clock is set. A background `clock-watch` thread samples it
+//! every second and calls the hooks registered with [`on_clock_jump`] when it
+//! has moved by more than their threshold.
+//!
+//! On Linux the time since boot is read from `CLOCK_BOOTTIME`, which keeps
+//! counting while the system is suspended. Other Unix systems use the
+//! monotonic [`uptime_duration`](super::uptime::uptime_duration()), which stops
+//! during suspend, so resuming shows up there as a forward jump by the time
+//! spent asleep.
+
+use std::fmt;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use super::uptime::uptime_duration;
+
+/// How often the watcher thread compares the clocks.
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+type Hook = Arc<dyn Fn(&ClockJump) + Send + Sync>;
+
+/// A hook with its threshold and the offset it last saw, in nanoseconds.
+struct Subscription {
+    threshold: Duration,
+    offset: i128,
+    hook: Hook,
+}
+
+static SUBSCRIPTIONS: Mutex<Vec<Subscription>> = Mutex::new(Vec::new());
+static WATCHER: OnceLock<()> = OnceLock::new();
+
+/// A change of the wall clock relative to the time since boot.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockJump {
+    /// What the wall clock would read had it not been set.
+    pub expected: SystemTime,
+    /// What it reads now.
+    pub actual: SystemTime,
+    /// The uptime when the jump was detected.
+    pub uptime: Duration,
+}
+
+impl ClockJump {
+    /// Returns `true` if the clock was set ahead.
+    pub fn is_forward(&self) -> bool {
+        self.actual >= self.expected
+    }
+
+    /// Returns how far the clock moved, in either direction.
+    pub fn amount(&self) -> Duration {
+        self.actual
+            .duration_since(self.expected)
+            .or_else(|_| self.expected.duration_since(self.actual))
+            .unwrap_or_default()
+    }
+
+    /// Returns how far the clock moved in milliseconds, negative if it was set back.
+    pub fn millis(&self) -> i64 {
+        let millis = self.amount().as_millis() as i64;
+        if self.is_forward() {
+            millis
+        } else {
+            -millis
+        }
+    }
+}
+
+impl fmt::Display for ClockJump {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let direction = if self.is_forward() { "forward" } else { "back" };
+        write!(f, "{direction} by {:?}", self.amount())
+    }
+}
+
+/// Registers `hook` to be called whenever the wall clock moves by more than
+/// `threshold` relative to the time since boot.
+///
+/// Hooks run on the `clock-watch` thread, started by the first registration.
+/// Each compares against the offset when it was registered or last called, so
+/// slow drift is reported too once it adds up to `threshold`. A panicking hook
+/// does not affect the others.
+///
+/// # Examples
+///
+/// ```no_run
+/// use std::time::Duration;
+///
+/// alumy::sys::clock::on_clock_jump(Duration::from_secs(1), |jump| {
+///     eprintln!("clock jumped {jump}");
+/// });
+/// ```
+pub fn on_clock_jump(threshold: Duration, hook: impl Fn(&ClockJump) + Send + Sync + 'static) {
+    WATCHER.get_or_init(spawn_watcher);
+
+    let offset = offset(SystemTime::now(), since_boot());
+    let mut subscriptions = SUBSCRIPTIONS.lock().unwrap_or_else(|e| e.into_inner());
+    subscriptions.push(Subscription { threshold, offset, hook: Arc::new(hook) });
+}
+
+fn spawn_watcher() {
+    thread::Builder::new()
+        .name("clock-watch".to_string())
+        .spawn(|| loop {
+            thread::sleep(POLL_INTERVAL);
+            // The hooks run without the lock, so they may register hooks themselves.
+            let due = {
+                let mut subscriptions = SUBSCRIPTIONS.lock().unwrap_or_else(|e| e.into_inner());
+                check(&mut subscriptions, SystemTime::now(), since_boot(), uptime_duration())
+            };
+            notify(due);
+        })
+        .expect("Failed to spawn clock watch thread");
+}
+
+/// Returns the time since boot, including suspend where the platform allows it.
+fn since_boot() -> Duration {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    {
+        let mut ts = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        if unsafe { libc::clock_gettime(libc::CLOCK_BOOTTIME, &mut ts) } == 0 {
+            return Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32);
+        }
+    }
+    uptime_duration()
+}
+
+/// Returns the hooks whose threshold the offset at `now` exceeds, with their jump.
+fn check(
+    subscriptions: &mut [Subscription],
+    now: SystemTime,
+    since_boot: Duration,
+    uptime: Duration,
+) -> Vec<(Hook, ClockJump)> {
+    let current = offset(now, since_boot);
+    let mut due = Vec::new();
+    for subscription in subscriptions {
+        let delta = current - subscription.offset;
+        if delta.unsigned_abs() <= subscription.threshold.as_nanos() {
+            continue;
+        }
+
+        let amount = Duration::from_nanos(delta.unsigned_abs().min(u64::MAX as u128) as u64);
+        let expected = if delta > 0 { now.checked_sub(amount) } else { now.checked_add(amount) };
+        let jump = ClockJump { expected: expected.unwrap_or(now), actual: now, uptime };
+        subscription.offset = current;
+        due.push((subscription.hook.clone(), jump));
+    }
+    due
+}
+
+/// Calls each hook with its jump; a panicking hook does not stop the others.
+fn notify(due: Vec<(Hook, ClockJump)>) {
+    for (hook, jump) in due {
+        let _ = catch_unwind(AssertUnwindSafe(|| hook(&jump)));
+    }
+}
+
+/// Returns the wall clock minus the time since boot, in nanoseconds since the epoch.
+fn offset(now: SystemTime, since_boot: Duration) -> i128 {
+    let wall = match now.duration_since(UNIX_EPOCH) {
+        Ok(since) => since.as_nanos() as i128,
+        Err(e) => -(e.duration().as_nanos() as i128),
+    };
+    wall - since_boot.as_nanos() as i128
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn subscription(threshold: Duration, start: SystemTime, jumps: Arc<Mutex<Vec<ClockJump>>>) -> Subscription {
+        Subscription {
+            threshold,
+            offset: offset(start, Duration::ZERO),
+            hook: Arc::new(move |jump| jumps.lock().unwrap().push(*jump)),
+        }
+    }
+
+    /// Runs one round of the watcher with the uptime equal to the time since boot.
+    fn poll(subscriptions: &mut [Subscription], now: SystemTime, since_boot: Duration) {
+        notify(check(subscriptions, now, since_boot, since_boot));
+    }
+
+    #[test]
+    fn test_check() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let jumps = Arc::new(Mutex::new(Vec::new()));
+        let mut subscriptions = [
+            subscription(Duration::from_secs(1), start, jumps.clone()),
+            subscription(Duration::from_secs(60), start, Arc::default()),
+        ];
+
+        // Both clocks advance together: no jump.
+        poll(&mut subscriptions, start + Duration::from_secs(10), Duration::from_secs(10));
+        assert!(jumps.lock().unwrap().is_empty());
+
+        // The wall clock is set back by 5 seconds.
+        let now = start + Duration::from_secs(15);
+        poll(&mut subscriptions, now, Duration::from_secs(20));
+        let jump = jumps.lock().unwrap()[0];
+        assert!(!jump.is_forward());
+        assert_eq!(jump.amount(), Duration::from_secs(5));
+        assert_eq!(jump.millis(), -5000);
+        assert_eq!(jump.expected, start + Duration::from_secs(20));
+        assert_eq!(jump.to_string(), "back by 5s");
+
+        // Drift below the threshold is reported once it adds up.
+        poll(&mut subscriptions, now + Duration::from_millis(1600), Duration::from_millis(21_000));
+        assert_eq!(jumps.lock().unwrap().len(), 1);
+        poll(&mut subscriptions, now + Duration::from_millis(2200), Duration::from_millis(21_000));
+        let jumps = jumps.lock().unwrap();
+        assert_eq!(jumps.len(), 2);
+        assert!(jumps[1].is_forward());
+        assert_eq!(jumps[1].millis(), 1200);
+    }
+
+    #[test]
+    fn test_panicking_hook() {
+        let start = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let jumps = Arc::new(Mutex::new(Vec::new()));
+        let mut subscriptions = [
+            Subscription { threshold: Duration::ZERO, offset: offset(start, Duration::ZERO), hook: Arc::new(|_| panic!("hook failed")) },
+            subscription(Duration::ZERO, start, jumps.clone()),
+        ];
+
+        poll(&mut subscriptions, start + Duration::from_secs(3600), Duration::ZERO);
+        assert_eq!(jumps.lock().unwrap().len(), 1);
+    }
+}
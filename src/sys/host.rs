@@ -0,0 +1,61 @@
+/// Returns the host name of the machine, if it can be determined.
+///
+/// On Unix-like systems it uses `libc::gethostname`.
+/// On Windows, it reads the `COMPUTERNAME` environment variable.
+pub fn hostname() -> Option<String> {
+    #[cfg(unix)]
+    {
+        let mut buf = [0u8; 256];
+        if unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } != 0 {
+            return None;
+        }
+        let len = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+        let name = String::from_utf8_lossy(&buf[..len]).into_owned();
+        (!name.is_empty()).then_some(name)
+    }
+
+    #[cfg(not(unix))]
+    {
+        std::env::var("COMPUTERNAME").ok().filter(|s| !s.is_empty())
+    }
+}
+
+/// Returns the identifier of the current boot, if the platform provides one.
+///
+/// On Linux, it reads `/proc/sys/kernel/random/boot_id`, which changes on every boot.
+/// Other platforms return `None`.
+pub fn boot_id() -> Option<String> {
+    #[cfg(target_os = "linux")]
+    {
+        std::fs::read_to_string("/proc/sys/kernel/random/boot_id")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    #[cfg(unix)]
+    fn test_hostname() {
+        let name = hostname().expect("hostname should be available");
+        assert!(!name.is_empty());
+        assert!(!name.contains('\0'));
+    }
+
+    #[test]
+    #[cfg(target_os = "linux")]
+    fn test_boot_id() {
+        let id = boot_id().expect("boot id should be available on Linux");
+        assert_eq!(id.len(), 36);
+        assert_eq!(boot_id(), Some(id));
+    }
+}
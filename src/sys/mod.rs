@@ -1,3 +1,5 @@
 //! System-level utilities for cross-platform development.
 
 pub mod uptime;
+pub mod clock;
+pub mod host;
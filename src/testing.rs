@@ -0,0 +1,267 @@
+//! Helpers for testing code that logs.
+//!
+//! [`LogCapture`] records events from a scoped subscriber into memory so tests
+//! can assert on them with [`assert_logged!`](crate::assert_logged) instead of
+//! reading log files, and [`TempLogDir`] provides a log directory that removes
+//! itself when dropped, for tests that do need a file.
+//!
+//! Enabled with the `testing` feature.
+
+use std::cell::RefCell;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use tracing::subscriber::DefaultGuard;
+use tracing::{Event, Level, Subscriber};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::Layer;
+
+use crate::log::{LogRecord, RecordVisitor};
+
+type Records = Arc<Mutex<Vec<LogRecord>>>;
+
+thread_local! {
+    /// Captures active on this thread, innermost last.
+    static ACTIVE: RefCell<Vec<Records>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Captures every event logged on the current thread while it is alive.
+///
+/// The capture installs a thread-local default subscriber that records events
+/// at all levels, ignoring any global logger. Dropping it restores the previous
+/// subscriber.
+///
+/// # Examples
+///
+/// ```
+/// use alumy::testing::LogCapture;
+/// use alumy::{assert_logged, Level};
+///
+/// let capture = LogCapture::start();
+/// alumy::warn!(port = "/dev/ttyS0", "device busy");
+///
+/// assert_logged!(Level::WARN, "device busy");
+/// assert_eq!(capture.records()[0].fields[0].1, "/dev/ttyS0");
+/// ```
+pub struct LogCapture {
+    records: Records,
+    _guard: DefaultGuard,
+}
+
+impl LogCapture {
+    /// Starts capturing events on the current thread.
+    pub fn start() -> Self {
+        let records = Records::default();
+        let layer = CaptureLayer(records.clone());
+        let guard = tracing::subscriber::set_default(tracing_subscriber::registry().with(layer));
+        ACTIVE.with(|active| active.borrow_mut().push(records.clone()));
+        Self { records, _guard: guard }
+    }
+
+    /// Returns a copy of the events captured so far.
+    pub fn records(&self) -> Vec<LogRecord> {
+        self.records.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Returns `true` if an event at `level` contains `text` in its message or fields.
+    pub fn contains(&self, level: Level, text: &str) -> bool {
+        matches(&self.records(), level, text)
+    }
+
+    /// Discards the events captured so far.
+    pub fn clear(&self) {
+        self.records.lock().unwrap_or_else(|e| e.into_inner()).clear();
+    }
+}
+
+impl Drop for LogCapture {
+    fn drop(&mut self) {
+        ACTIVE.with(|active| {
+            active.borrow_mut().retain(|records| !Arc::ptr_eq(records, &self.records));
+        });
+    }
+}
+
+struct CaptureLayer(Records);
+
+impl<S: Subscriber> Layer<S> for CaptureLayer {
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut record = LogRecord {
+            level: *event.metadata().level(),
+            target: event.metadata().target().to_string(),
+            message: String::new(),
+            fields: Vec::new(),
+        };
+        event.record(&mut RecordVisitor(&mut record));
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).push(record);
+    }
+}
+
+fn matches(records: &[LogRecord], level: Level, text: &str) -> bool {
+    records.iter().any(|record| {
+        record.level == level
+            && (record.message.contains(text)
+                || record.fields.iter().any(|(key, value)| format!("{key}={value}").contains(text)))
+    })
+}
+
+#[doc(hidden)]
+pub fn __assert_logged(level: Level, text: &str) {
+    let records = ACTIVE.with(|active| active.borrow().last().cloned());
+    let Some(records) = records else {
+        panic!("assert_logged! requires an active LogCapture on this thread");
+    };
+
+    let records = records.lock().unwrap_or_else(|e| e.into_inner());
+    if !matches(&records, level, text) {
+        let logged: Vec<_> = records
+            .iter()
+            .map(|r| format!("  {} {}: {} {:?}", r.level, r.target, r.message, r.fields))
+            .collect();
+        panic!("no {level} event containing {text:?} was logged, captured:\n{}", logged.join("\n"));
+    }
+}
+
+/// Asserts that the innermost [`LogCapture`](crate::testing::LogCapture) on this
+/// thread saw an event at `level` whose message or fields contain `text`.
+///
+/// On failure the panic message lists everything that was captured.
+#[macro_export]
+macro_rules! assert_logged {
+    ($level:expr, $text:expr $(,)?) => {
+        $crate::testing::__assert_logged($level, ::core::convert::AsRef::<str>::as_ref(&$text))
+    };
+}
+
+/// A uniquely named directory under the system temp dir, removed when dropped.
+///
+/// # Examples
+///
+/// ```no_run
+/// use alumy::testing::TempLogDir;
+/// use std::time::Duration;
+///
+/// let dir = TempLogDir::new("app");
+/// alumy::LogConfig::new("app", "info")
+///     .with_file(dir.file("app.log").to_str().unwrap(), "1M", 2)
+///     .init()
+///     .unwrap();
+///
+/// alumy::info!("started");
+/// assert!(dir.wait_for("app.log", "started", Duration::from_secs(1)).is_some());
+/// ```
+#[derive(Debug)]
+pub struct TempLogDir {
+    path: PathBuf,
+}
+
+impl TempLogDir {
+    /// Creates an empty directory whose name starts with `name`.
+    pub fn new(name: &str) -> Self {
+        static COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+        let unique = format!("alumy-{name}-{}-{}", std::process::id(), COUNTER.fetch_add(1, Ordering::Relaxed));
+        let path = std::env::temp_dir().join(unique);
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).expect("Failed to create temporary log directory");
+        Self { path }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Returns the path of `name` inside the directory.
+    pub fn file(&self, name: &str) -> PathBuf {
+        self.path.join(name)
+    }
+
+    /// Reads `name`, returning an empty string if it does not exist yet.
+    pub fn read(&self, name: &str) -> String {
+        fs::read_to_string(self.file(name)).unwrap_or_default()
+    }
+
+    /// Polls `name` until a line containing `text` appears, returning that line.
+    ///
+    /// File logging goes through a background writer, so this replaces a fixed
+    /// sleep before reading the file. Returns `None` after `timeout`.
+    pub fn wait_for(&self, name: &str, text: &str, timeout: Duration) -> Option<String> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(line) = self.read(name).lines().find(|line| line.contains(text)) {
+                return Some(line.to_string());
+            }
+            if Instant::now() >= deadline {
+                return None;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
+
+impl Drop for TempLogDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_capture_and_assert() {
+        let capture = LogCapture::start();
+        tracing::info!("service started");
+        tracing::error!(code = 7, "device failed");
+
+        crate::assert_logged!(Level::INFO, "started");
+        crate::assert_logged!(Level::ERROR, "code=7");
+        assert!(!capture.contains(Level::WARN, "device failed"));
+        assert_eq!(capture.records().len(), 2);
+
+        capture.clear();
+        assert!(capture.records().is_empty());
+    }
+
+    #[test]
+    fn test_nested_captures() {
+        let outer = LogCapture::start();
+        tracing::info!("outer event");
+        {
+            let inner = LogCapture::start();
+            tracing::info!("inner event");
+            crate::assert_logged!(Level::INFO, "inner event");
+            assert_eq!(inner.records().len(), 1);
+        }
+        tracing::info!("outer again");
+        crate::assert_logged!(Level::INFO, "outer again");
+        assert_eq!(outer.records().len(), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "no WARN event containing \"missing\"")]
+    fn test_assert_logged_fails() {
+        let _capture = LogCapture::start();
+        tracing::info!("something else");
+        crate::assert_logged!(Level::WARN, "missing");
+    }
+
+    #[test]
+    fn test_temp_log_dir() {
+        let dir = TempLogDir::new("fixture");
+        let path = dir.path().to_path_buf();
+        assert!(path.is_dir());
+
+        fs::write(dir.file("app.log"), "first\nsecond line\n").unwrap();
+        assert_eq!(dir.wait_for("app.log", "second", Duration::from_millis(100)).as_deref(), Some("second line"));
+        assert!(dir.wait_for("app.log", "third", Duration::from_millis(50)).is_none());
+
+        drop(dir);
+        assert!(!path.exists());
+    }
+}
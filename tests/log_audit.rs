@@ -0,0 +1,58 @@
+mod common;
+use alumy::log::audit::AuditKey;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_audit() {
+    let log_dir = "test_logs_audit";
+    let log_file = "test_logs_audit/app.log";
+    let audit_file = "test_logs_audit/audit.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let key = AuditKey::new(*b"integration key");
+    let config = LogConfig::new("test_audit", "warn")
+        .with_filter("warn,app=debug")
+        .with_file(log_file, "1K", 5)
+        .with_audit("audit", audit_file, key.clone());
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..30 {
+        tracing::info!(target: "audit", user = "alice", "Permission granted {i}");
+    }
+    tracing::debug!(target: "audit::login", "Login accepted");
+    tracing::warn!("Regular warning");
+    tracing::info!(target: "auditor", "Not an audit event");
+    tracing::trace!(target: "auditor", "Auditor trace");
+
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).unwrap();
+    assert!(content.contains("Regular warning"));
+    assert!(!content.contains("Permission granted"), "Audit events should stay out of the regular log");
+    assert!(
+        !content.contains("Not an audit event") && !content.contains("Auditor trace"),
+        "Targets only starting with the audit target should not bypass the level filter"
+    );
+
+    let audit = fs::read_to_string(audit_file).unwrap();
+    assert!(audit.contains("Login accepted"), "Audit events should bypass the level filter");
+    assert!(!audit.contains("Regular warning") && !audit.contains("Not an audit event"));
+
+    let report = alumy::log::audit::verify(audit_file, &key).unwrap();
+    assert!(report.is_intact(), "{:?}", report.broken);
+    assert_eq!(report.records, 31);
+    assert!(report.files.len() > 1, "Audit log should have rotated");
+
+    let rotated = &report.files[0];
+    let tampered = fs::read_to_string(rotated).unwrap().replacen("alice", "mallory", 1);
+    fs::write(rotated, tampered).unwrap();
+
+    let broken = alumy::log::audit::verify(audit_file, &key).unwrap().broken.expect("Tampering should be detected");
+    assert_eq!(&broken.file, rotated);
+    assert_eq!(broken.line, 2);
+}
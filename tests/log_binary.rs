@@ -0,0 +1,52 @@
+mod common;
+use alumy::log::{LogConfig, LogFormat};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_binary() {
+    let log_dir = "test_logs_binary";
+    let log_file = "test_logs_binary/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_binary", "info")
+        .with_file(log_file, "16K", 3)
+        .with_format(LogFormat::Binary);
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..2000u64 {
+        tracing::info!(target: "sensor", seq = i, channel = "adc0", "sample taken");
+    }
+    tracing::warn!(target: "sensor", "Binary done");
+
+    thread::sleep(Duration::from_millis(200));
+
+    // Every file, including rotated ones, can be decoded on its own.
+    let mut binary_size = 0;
+    let mut text_size = 0;
+    let mut files = 0;
+    for entry in fs::read_dir(log_dir).unwrap() {
+        let path = entry.unwrap().path();
+        binary_size += fs::metadata(&path).unwrap().len() as usize;
+        let events = alumy::log::decode_binary(&path).expect("Failed to decode binary log");
+        assert!(!events.is_empty(), "{} has no events", path.display());
+        text_size += events.iter().map(|e| e.to_string().len() + 1).sum::<usize>();
+        files += 1;
+    }
+    assert!(files > 1, "Should have rotated");
+
+    let events = alumy::log::decode_binary(log_file).unwrap();
+    let last = events.last().unwrap();
+    assert_eq!(last.message, "Binary done");
+    assert!(last.to_string().contains(" WARN sensor: Binary done"), "{last}");
+    let uptime = alumy::sys::uptime::uptime_duration();
+    assert!(last.uptime <= uptime && uptime - last.uptime < Duration::from_secs(5), "Bad uptime {:?}", last.uptime);
+    assert!(events.windows(2).all(|w| w[0].uptime <= w[1].uptime));
+    let sample = &events[events.len() - 2];
+    assert_eq!(sample.fields, [("seq".to_string(), "1999".to_string()), ("channel".to_string(), "\"adc0\"".to_string())]);
+
+    assert!(binary_size * 5 < text_size, "Binary {binary_size} bytes vs text {text_size} bytes");
+}
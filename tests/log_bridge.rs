@@ -0,0 +1,37 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_bridge() {
+    let log_dir = "test_logs_bridge";
+    let log_file = "test_logs_bridge/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_bridge", "info")
+        .with_file(log_file, "1M", 2)
+        .with_filter("info,dependency=debug")
+        .with_target(true)
+        .with_log_bridge(true);
+
+    config.init().expect("Failed to initialize logger");
+
+    assert_eq!(log::max_level(), log::LevelFilter::Debug, "Max level should follow the filter");
+
+    log::debug!(target: "dependency", "Debug from log crate should appear");
+    log::debug!(target: "other", "Debug from log crate should NOT appear");
+    log::info!(target: "other", "Info from log crate should appear");
+    log::trace!(target: "dependency", "Trace from log crate should NOT appear");
+
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).expect("Failed to read log file");
+
+    assert!(content.contains("Debug from log crate should appear"), "Bridged debug message missing");
+    assert!(content.contains("dependency"), "Bridged target missing");
+    assert!(content.contains("Info from log crate should appear"), "Bridged info message missing");
+    assert!(!content.contains("should NOT appear"), "Filtered log record present");
+}
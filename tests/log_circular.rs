@@ -0,0 +1,33 @@
+mod common;
+use alumy::log::{FileMode, LogConfig};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_circular() {
+    let log_dir = "test_logs_circular";
+    let log_file = "test_logs_circular/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_circular", "info")
+        .with_file(log_file, "16K", 1)
+        .with_file_mode(FileMode::Circular);
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..1000 {
+        tracing::info!("Circular message {i:04}");
+    }
+
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(fs::metadata(log_file).unwrap().len(), 16 * 1024, "File size should stay fixed");
+    assert_eq!(fs::read_dir(log_dir).unwrap().count(), 1, "No rotated files expected");
+
+    let records = alumy::log::read_circular(log_file).expect("Failed to read circular log");
+    assert!(records.len() > 50 && records.len() < 1000, "Unexpected record count {}", records.len());
+    assert!(records.last().unwrap().ends_with("Circular message 0999"));
+    assert!(records.windows(2).all(|w| w[0] < w[1]), "Records should be in order");
+}
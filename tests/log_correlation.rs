@@ -0,0 +1,39 @@
+mod common;
+use alumy::log::{request_span, LogConfig, LogFormat, TraceParent};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_correlation() {
+    let log_dir = "test_logs_correlation";
+    let log_file = "test_logs_correlation/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_correlation", "info")
+        .with_file(log_file, "1M", 2)
+        .with_format(LogFormat::Logfmt);
+
+    config.init().expect("Failed to initialize logger");
+
+    let incoming = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+    let span = request_span("req-42", Some(incoming));
+    let outgoing = span.in_scope(|| {
+        tracing::info!("Handling request");
+        TraceParent::current().expect("Trace context missing")
+    });
+
+    assert_eq!(outgoing.trace_id, 0x4bf92f3577b34da6a3ce929d0e0e4736);
+    assert_ne!(outgoing.parent_id, 0x00f067aa0ba902b7, "The local span should become the parent");
+    assert!(outgoing.sampled);
+
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).expect("Failed to read log file");
+    let line = content.lines().find(|l| l.contains("Handling request")).expect("Log message missing");
+    assert!(
+        line.ends_with(&format!("span=request request_id=req-42 traceparent={incoming}")),
+        "Unexpected line: {line}"
+    );
+}
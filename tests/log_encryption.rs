@@ -0,0 +1,52 @@
+#![cfg(feature = "encryption")]
+
+mod common;
+use alumy::log::{decrypt_file, generate_keypair, DecryptionKey, EncryptionKey, LogConfig};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_encryption() {
+    let log_dir = "test_logs_encryption";
+    let log_file = "test_logs_encryption/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let (secret, public) = generate_keypair().unwrap();
+    let config = LogConfig::new("test_encryption", "info")
+        .with_file(log_file, "2K", 10)
+        .with_header(true)
+        .with_encryption(EncryptionKey::Public(public));
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..20 {
+        tracing::info!("Secret message {:02} with enough content", i);
+        thread::sleep(Duration::from_millis(5));
+    }
+
+    thread::sleep(Duration::from_millis(500));
+
+    // Oldest first: test.log.N, ..., test.log.1, test.log.
+    let mut files: Vec<_> = fs::read_dir(log_dir).unwrap().map(|e| e.unwrap().path()).collect();
+    assert!(files.len() > 1, "Should have rolled at least once");
+    files.sort_by_key(|p| std::cmp::Reverse(p.extension().and_then(|e| e.to_str()?.parse::<u32>().ok()).unwrap_or(0)));
+
+    let mut text = String::new();
+    for file in &files {
+        assert!(!fs::read_to_string(file).unwrap_or_default().contains("Secret message"));
+        let plain = decrypt_file(file, &DecryptionKey::Secret(secret)).expect("Failed to decrypt log file");
+        assert_eq!(plain.skipped, 0);
+        // Rotated files were closed cleanly; the active one is still being written.
+        let rotated = file.extension().is_some_and(|e| e != "log");
+        assert_eq!(plain.unfinished, usize::from(!rotated), "{}", file.display());
+        let plain = String::from_utf8(plain.data).unwrap();
+        assert!(plain.starts_with("# "), "Every file should start with the header: {plain}");
+        text.push_str(&plain);
+    }
+
+    let messages: Vec<_> = text.lines().filter(|l| l.contains("Secret message")).collect();
+    assert_eq!(messages.len(), 20, "{text}");
+    assert!(messages[19].contains("Secret message 19"));
+}
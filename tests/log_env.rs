@@ -0,0 +1,34 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_env_overrides() {
+    let log_dir = "test_logs_env";
+    let conf_file = "test_logs_env/app.conf";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+    fs::create_dir_all(log_dir).unwrap();
+
+    fs::write(conf_file, "level = warn\nfile = test_logs_env/from-conf.log\nmax_size = 1M\nmax_files = 2\n").unwrap();
+    std::env::set_var("TEST_ENV_APP_LOG", "debug");
+    std::env::set_var("TEST_ENV_APP_LOG_FILE", "test_logs_env/from-env.log");
+    std::env::set_var("TEST_ENV_APP_LOG_FORMAT", "logfmt");
+
+    let config = LogConfig::new("test-env-app", "error")
+        .with_config_file(conf_file)
+        .with_env(true);
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::debug!("Debug enabled by environment");
+
+    thread::sleep(Duration::from_millis(200));
+
+    assert!(!std::path::Path::new("test_logs_env/from-conf.log").exists());
+    let content = fs::read_to_string("test_logs_env/from-env.log").unwrap();
+    assert!(content.contains("level=debug"), "Format and filter should come from the environment: {content}");
+    assert!(content.contains("msg=\"Debug enabled by environment\""));
+}
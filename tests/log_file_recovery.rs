@@ -0,0 +1,31 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_file_recreated_after_delete() {
+    let log_dir = "test_logs_recovery";
+    let log_file = "test_logs_recovery/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_recovery", "info")
+        .with_file(log_file, "1M", 2);
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::info!("Before delete");
+    thread::sleep(Duration::from_millis(200));
+    fs::remove_dir_all(log_dir).expect("Failed to remove log directory");
+
+    // The sink checks for deletion at most once per second.
+    thread::sleep(Duration::from_millis(1100));
+    tracing::info!("After delete");
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).expect("Log file was not recreated");
+    assert!(!content.contains("Before delete"), "Old content should be gone");
+    assert!(content.contains("After delete"), "Message after recovery missing");
+}
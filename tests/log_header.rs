@@ -0,0 +1,40 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_header_on_rotation() {
+    let log_dir = "test_logs_header";
+    let log_file = "test_logs_header/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_header", "info")
+        .with_file(log_file, "2K", 3)
+        .with_version("1.2.3")
+        .with_header_hook(|| "# build: test-build-42\n".to_string());
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..40 {
+        tracing::info!("Header message {:02} with enough content to rotate", i);
+    }
+
+    thread::sleep(Duration::from_millis(500));
+
+    let mut files = 0;
+    for entry in fs::read_dir(log_dir).expect("Failed to read log directory") {
+        let content = fs::read_to_string(entry.unwrap().path()).expect("Failed to read log file");
+        assert!(content.starts_with("# log opened "), "File does not start with header");
+        assert!(content.contains("# app=test_header version=1.2.3"), "Identity missing from header");
+        assert!(
+            content.contains("# config: filter=\"info\" file=\"test_logs_header/test.log\" mode=Rolling max_size=2048 max_files=3 format=Text\n"),
+            "Config missing from header"
+        );
+        assert!(content.contains("# build: test-build-42"), "Header hook output missing");
+        files += 1;
+    }
+    assert!(files > 1, "Should have rotated at least once");
+}
@@ -0,0 +1,35 @@
+use alumy::log::LogConfig;
+use alumy::Level;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_event_hooks() {
+    let config = LogConfig::new("test_hooks", "info");
+    config.init().expect("Failed to initialize logger");
+
+    let errors = Arc::new(AtomicUsize::new(0));
+    let warnings = Arc::new(Mutex::new(Vec::new()));
+
+    let counter = errors.clone();
+    alumy::log::on_event(Level::ERROR, move |_| {
+        counter.fetch_add(1, Ordering::SeqCst);
+    });
+
+    let seen = warnings.clone();
+    alumy::log::on_event(Level::WARN, move |record| {
+        seen.lock().unwrap().push(record.message.clone());
+    });
+
+    alumy::info!("Not a hook event");
+    alumy::warn!("Disk almost full");
+    alumy::error!(errno = 28, "Disk full");
+    alumy::debug!("Filtered out");
+
+    thread::sleep(Duration::from_millis(200));
+
+    assert_eq!(errors.load(Ordering::SeqCst), 1, "Error hook count mismatch");
+    assert_eq!(*warnings.lock().unwrap(), ["Disk almost full", "Disk full"], "Warn hook records mismatch");
+}
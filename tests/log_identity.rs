@@ -0,0 +1,36 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_identity() {
+    let log_dir = "test_logs_identity";
+    let log_file = "test_logs_identity/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_identity", "info")
+        .with_file(log_file, "1M", 2)
+        .with_version("1.2.3")
+        .with_identity(true);
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::info!("Identity message");
+
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).expect("Failed to read log file");
+    let line = content.lines().find(|l| l.contains("Identity message")).expect("Log message missing");
+
+    assert!(line.starts_with("app=test_identity version=1.2.3 "), "Identity stamp missing: {line}");
+    assert!(line.contains(&format!("pid={}", std::process::id())), "PID missing");
+    if let Some(host) = alumy::sys::host::hostname() {
+        assert!(line.contains(&format!("host={host}")), "Hostname missing");
+    }
+    if let Some(boot_id) = alumy::sys::host::boot_id() {
+        assert!(line.contains(&format!("boot={boot_id}")), "Boot ID missing");
+    }
+}
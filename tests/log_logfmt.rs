@@ -0,0 +1,36 @@
+mod common;
+use alumy::log::{LogConfig, LogFormat};
+use std::fs;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_logfmt() {
+    let log_dir = "test_logs_logfmt";
+    let log_file = "test_logs_logfmt/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    let config = LogConfig::new("test_logfmt", "info")
+        .with_file(log_file, "1M", 2)
+        .with_target(true)
+        .with_time_format("uptime")
+        .with_format(LogFormat::Logfmt);
+
+    config.init().expect("Failed to initialize logger");
+
+    let span = tracing::info_span!("job", name = "nightly backup");
+    let _enter = span.enter();
+    tracing::info!(target: "app", port = "/dev/ttyS0", "Logfmt message");
+
+    thread::sleep(Duration::from_millis(200));
+
+    let content = fs::read_to_string(log_file).expect("Failed to read log file");
+    let line = content.lines().find(|l| l.contains("Logfmt message")).expect("Log message missing");
+
+    assert!(line.starts_with("ts="), "Timestamp missing: {line}");
+    assert!(
+        line.ends_with(" level=info target=app msg=\"Logfmt message\" port=/dev/ttyS0 span=job name=\"nightly backup\""),
+        "Unexpected logfmt line: {line}"
+    );
+}
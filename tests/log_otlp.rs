@@ -0,0 +1,51 @@
+#![cfg(feature = "otlp")]
+
+use alumy::log::{LogConfig, OtlpConfig};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_otlp() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}/collector", listener.local_addr().unwrap());
+    let (sender, receiver) = mpsc::channel();
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let mut reader = BufReader::new(stream.unwrap());
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+
+            let mut length = 0;
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap() > 2 {
+                if let Some(value) = header.to_ascii_lowercase().strip_prefix("content-length:") {
+                    length = value.trim().parse().unwrap();
+                }
+                header.clear();
+            }
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.get_mut().write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+            sender.send((request_line, String::from_utf8(body).unwrap())).unwrap();
+        }
+    });
+
+    let config = LogConfig::new("test_otlp", "info")
+        .with_version("1.2.3")
+        .with_otlp(OtlpConfig::new(endpoint).with_batch_timeout(Duration::from_millis(50)));
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::info!(device = "/dev/ttyS0", "Exported message");
+
+    let (request_line, body) = receiver.recv_timeout(Duration::from_secs(5)).expect("No export received");
+    assert!(request_line.starts_with("POST /collector/v1/logs "));
+    assert!(body.contains(r#""key":"service.name","value":{"stringValue":"test_otlp"}"#), "{body}");
+    assert!(body.contains(r#""key":"service.version","value":{"stringValue":"1.2.3"}"#));
+    assert!(body.contains(r#""body":{"stringValue":"Exported message"}"#));
+    assert!(body.contains(r#""key":"device","value":{"stringValue":"/dev/ttyS0"}"#));
+}
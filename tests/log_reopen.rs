@@ -0,0 +1,49 @@
+#![cfg(unix)]
+mod common;
+use alumy::log::{FileMode, LogConfig};
+use std::fs;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread;
+use std::time::Duration;
+
+static APP_HANDLER_CALLED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn app_sighup(_: libc::c_int) {
+    APP_HANDLER_CALLED.store(true, Ordering::SeqCst);
+}
+
+#[test]
+fn test_log_reopen_after_external_rotation() {
+    let log_dir = "test_logs_reopen";
+    let log_file = "test_logs_reopen/app.log";
+    let rotated_file = "test_logs_reopen/app.log.1";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    // A handler the application installed before the logger.
+    unsafe { libc::signal(libc::SIGHUP, app_sighup as extern "C" fn(libc::c_int) as libc::sighandler_t) };
+
+    let config = LogConfig::new("test_reopen", "info")
+        .with_file(log_file, "1M", 2)
+        .with_file_mode(FileMode::Reopen);
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::info!("Before rotation");
+    thread::sleep(Duration::from_millis(200));
+
+    // Simulate `logrotate` with `create`, followed by its postrotate SIGHUP.
+    fs::rename(log_file, rotated_file).expect("Failed to rotate log file");
+    unsafe { libc::raise(libc::SIGHUP) };
+
+    tracing::info!("After rotation");
+    thread::sleep(Duration::from_millis(200));
+
+    let rotated = fs::read_to_string(rotated_file).expect("Failed to read rotated log file");
+    let current = fs::read_to_string(log_file).expect("Log file was not reopened");
+
+    assert!(rotated.contains("Before rotation"), "Old message missing from rotated file");
+    assert!(!rotated.contains("After rotation"), "New message written to rotated file");
+    assert!(current.contains("After rotation"), "New message missing from reopened file");
+    assert!(APP_HANDLER_CALLED.load(Ordering::SeqCst), "Previous SIGHUP handler was not called");
+}
@@ -0,0 +1,69 @@
+#![cfg(unix)]
+mod common;
+use alumy::log::{FileMode, LogConfig};
+use std::env;
+use std::fs;
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+const CHILD_ENV: &str = "ALUMY_TEST_SHARED_CHILD";
+const LOG_DIR: &str = "test_logs_shared";
+const LOG_FILE: &str = "test_logs_shared/test.log";
+
+fn child(id: &str) {
+    LogConfig::new("test_shared", "info")
+        .with_file(LOG_FILE, "2K", 50)
+        .with_file_mode(FileMode::Shared)
+        .with_time(false)
+        .init()
+        .expect("Failed to initialize logger");
+
+    for i in 0..100 {
+        tracing::info!("process {id} message {i:03}");
+    }
+    thread::sleep(Duration::from_millis(300));
+}
+
+#[test]
+fn test_log_shared_between_processes() {
+    if let Ok(id) = env::var(CHILD_ENV) {
+        child(&id);
+        return;
+    }
+
+    let _guard = common::CleanupGuard(LOG_DIR);
+    common::setup_log_dir(LOG_DIR);
+
+    let exe = env::current_exe().expect("Failed to locate test binary");
+    let children: Vec<_> = (0..4)
+        .map(|id| {
+            Command::new(&exe)
+                .args(["test_log_shared_between_processes", "--exact", "--test-threads=1"])
+                .env(CHILD_ENV, id.to_string())
+                .stdout(Stdio::null())
+                .spawn()
+                .expect("Failed to spawn child process")
+        })
+        .collect();
+    for mut c in children {
+        assert!(c.wait().expect("Child process failed").success());
+    }
+
+    let mut lines = Vec::new();
+    for entry in fs::read_dir(LOG_DIR).expect("Failed to read log directory") {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|e| e == "lock") {
+            continue;
+        }
+        let content = fs::read_to_string(&path).expect("Failed to read log file");
+        lines.extend(content.lines().map(str::to_string));
+    }
+
+    assert_eq!(lines.len(), 400, "Lines were lost or duplicated");
+    for id in 0..4 {
+        let count = lines.iter().filter(|l| l.contains(&format!("process {id} message"))).count();
+        assert_eq!(count, 100, "Lines of process {id} missing");
+    }
+    assert!(lines.iter().all(|l| l.ends_with(char::is_numeric)), "Interleaved line found");
+}
@@ -0,0 +1,41 @@
+mod common;
+use alumy::log::LogConfig;
+use std::thread;
+use std::time::Duration;
+
+#[test]
+fn test_log_stats() {
+    let log_dir = "test_logs_stats";
+    let log_file = "test_logs_stats/test.log";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+
+    assert_eq!(alumy::log::stats(), alumy::log::LogStats::default());
+
+    let config = LogConfig::new("test_stats", "info")
+        .with_file(log_file, "500", 3);
+
+    config.init().expect("Failed to initialize logger");
+
+    for i in 0..20 {
+        tracing::info!("Stats message {:02} with enough content", i);
+    }
+
+    thread::sleep(Duration::from_millis(500));
+
+    let stats = alumy::log::stats();
+    assert_eq!(stats.queue_depth, 0, "Queue should be drained");
+    assert_eq!(stats.events_written, 20, "Events written mismatch");
+    assert!(stats.bytes_written > 20 * 30, "Bytes written too small");
+    assert_eq!(stats.write_errors, 0, "Unexpected write errors");
+    assert_eq!(stats.dropped_events, 0, "Unexpected dropped events");
+    assert!(stats.rotations > 0, "Should have rotated at least once");
+    assert!(stats.max_latency >= stats.last_latency, "Latency tracking inconsistent");
+
+    // A second init is ignored and must not take over the stats.
+    LogConfig::new("test_stats", "info")
+        .with_file("test_logs_stats/other.log", "500", 3)
+        .init()
+        .expect("Second init should be a no-op");
+    assert_eq!(alumy::log::stats().events_written, 20, "Stats taken over by an uninstalled logger");
+}
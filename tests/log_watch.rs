@@ -0,0 +1,51 @@
+mod common;
+use alumy::log::LogConfig;
+use std::fs;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Logs `message` at debug level until `expected` shows up in `file`, as reloads happen in the background.
+fn log_until_written(file: &str, message: &str, expected: &str) -> String {
+    let deadline = Instant::now() + Duration::from_secs(5);
+    loop {
+        tracing::debug!("{message}");
+        thread::sleep(Duration::from_millis(200));
+
+        let content = fs::read_to_string(file).unwrap_or_default();
+        if content.contains(expected) || Instant::now() > deadline {
+            return content;
+        }
+    }
+}
+
+#[test]
+fn test_log_watch() {
+    let log_dir = "test_logs_watch";
+    let log_file = "test_logs_watch/test.log";
+    let conf_file = "test_logs_watch/test.conf";
+    let _guard = common::CleanupGuard(log_dir);
+    common::setup_log_dir(log_dir);
+    fs::create_dir_all(log_dir).unwrap();
+    fs::write(conf_file, "level = info\n").unwrap();
+
+    let config = LogConfig::new("test_watch", "info")
+        .with_file(log_file, "1M", 2)
+        .watch(conf_file);
+
+    config.init().expect("Failed to initialize logger");
+
+    tracing::debug!("Hidden before reload");
+    fs::write(conf_file, "level = debug\nformat = logfmt\n").unwrap();
+
+    let content = log_until_written(log_file, "Shown after reload", "Shown after reload");
+    assert!(!content.contains("Hidden before reload"));
+    assert!(content.contains("level=debug"), "Format should have been reloaded: {content}");
+
+    fs::write(conf_file, "level = debug\nformat = yaml\n").unwrap();
+    let content = log_until_written(log_file, "Waiting for reload", "Keeping the previous log config");
+    assert!(content.contains("unknown log format `yaml`"), "Invalid config should be reported: {content}");
+
+    tracing::debug!("Still at debug");
+    thread::sleep(Duration::from_millis(200));
+    assert!(fs::read_to_string(log_file).unwrap().contains("msg=\"Still at debug\""));
+}